@@ -1,23 +1,82 @@
 use crossbeam_channel::Sender;
 use fnv::FnvHashMap;
-use jsonrpc_core::{self, Call, Id, Params, Version};
+use jsonrpc_core::{self, Call, Id, Output, Params, Version};
+use languageserver_types::notification::Notification;
+use languageserver_types::request::Request;
 use languageserver_types::*;
+use serde_json::Value;
+use std::path::PathBuf;
+use types::DocumentLink;
 use types::*;
 
+pub type BatchId = u64;
+
+/// Edits of files which are not opened in the editor, they are applied on disk.
+pub type FileEdits = Vec<(PathBuf, Vec<TextEdit>)>;
+
+/// Responses of requests which are handled together, collected until all of them arrive.
+pub struct Batch {
+    pub size: usize,
+    // NOTE buffer version requests were made for
+    pub version: u64,
+    pub responses: Vec<Value>,
+    // NOTE some of requests failed or got stale response, so responses are incomplete
+    pub failed: bool,
+}
+
+/// Requests of these methods are repeated as user types or moves cursor, so a newer request for
+/// the same buffer makes older ones obsolete.
+const SUPERSEDED_METHODS: &[&str] = &[
+    request::Completion::METHOD,
+    request::HoverRequest::METHOD,
+    request::SignatureHelpRequest::METHOD,
+    request::DocumentHighlightRequest::METHOD,
+    request::CodeLensRequest::METHOD,
+    request::DocumentLinkRequest::METHOD,
+    // NOTE incremental prompt sends request on each keystroke
+    request::WorkspaceSymbol::METHOD,
+    DOCUMENT_COLOR_METHOD,
+    INLAY_HINT_METHOD,
+    SEMANTIC_TOKENS_FULL_METHOD,
+    SEMANTIC_TOKENS_FULL_DELTA_METHOD,
+    SEMANTIC_TOKENS_RANGE_METHOD,
+];
+
 pub struct Context {
+    pub batch_counter: BatchId,
+    pub batches: FnvHashMap<BatchId, Batch>,
+    pub call_hierarchy: Hierarchy,
     pub capabilities: Option<ServerCapabilities>,
+    // NOTE buffer version code actions were computed for along with the actions themselves
+    pub code_actions: FnvHashMap<String, (u64, Vec<CodeActionOrCommand>)>,
+    // NOTE buffer version lenses were computed for along with the lenses themselves
+    pub code_lenses: FnvHashMap<String, (u64, Vec<CodeLens>)>,
+    pub completion: CompletionCache,
     pub config: Config,
     pub controller_poison_tx: Sender<()>,
     pub diagnostics: FnvHashMap<String, Vec<Diagnostic>>,
+    // NOTE text of each buffer as it was last sent to the language server
+    pub documents: FnvHashMap<String, String>,
+    pub document_colors: FnvHashMap<String, (u64, Vec<ColorInformation>)>,
+    pub document_links: FnvHashMap<String, (u64, Vec<DocumentLink>)>,
     pub editor_tx: Sender<EditorResponse>,
+    pub extended_capabilities: ExtendedServerCapabilities,
+    pub inlay_hints: FnvHashMap<String, InlayHintsCache>,
     pub lang_srv_poison_tx: Sender<()>,
     pub lang_srv_tx: Sender<ServerMessage>,
     pub language_id: String,
     pub pending_requests: Vec<EditorRequest>,
+    // NOTE requests from language server which are answered once editor reports back, along with
+    // edits of files which are not opened in the editor and are applied after buffers are edited
+    pub pending_server_requests: FnvHashMap<u64, (Id, FileEdits)>,
+    pub position_encoding: PositionEncoding,
     pub request_counter: u64,
     pub response_waitlist: FnvHashMap<Id, (EditorMeta, String, EditorParams)>,
     pub root_path: String,
+    pub selection_range_history: FnvHashMap<String, SelectionRangeHistory>,
+    pub semantic_tokens: FnvHashMap<String, SemanticTokensCache>,
     pub session: SessionId,
+    pub type_hierarchy: Hierarchy,
     pub versions: FnvHashMap<String, u64>,
 }
 
@@ -34,24 +93,41 @@ impl Context {
     ) -> Self {
         let session = initial_request.meta.session.clone();
         Context {
+            batch_counter: 0,
+            batches: FnvHashMap::default(),
+            call_hierarchy: Hierarchy::default(),
             capabilities: None,
+            code_actions: FnvHashMap::default(),
+            code_lenses: FnvHashMap::default(),
+            completion: CompletionCache::default(),
             config,
             controller_poison_tx,
             diagnostics: FnvHashMap::default(),
+            documents: FnvHashMap::default(),
+            document_colors: FnvHashMap::default(),
+            document_links: FnvHashMap::default(),
             editor_tx,
+            extended_capabilities: ExtendedServerCapabilities::default(),
+            inlay_hints: FnvHashMap::default(),
             lang_srv_poison_tx,
             lang_srv_tx,
             language_id: language_id.to_string(),
             pending_requests: vec![initial_request],
+            pending_server_requests: FnvHashMap::default(),
+            position_encoding: PositionEncoding::default(),
             request_counter: 0,
             response_waitlist: FnvHashMap::default(),
             root_path,
+            selection_range_history: FnvHashMap::default(),
+            semantic_tokens: FnvHashMap::default(),
             session,
+            type_hierarchy: Hierarchy::default(),
             versions: FnvHashMap::default(),
         }
     }
 
     pub fn call(&mut self, id: Id, method: String, params: impl ToParams) {
+        self.cancel_superseded_requests(&id, &method);
         let params = params.to_params();
         if params.is_err() {
             error!("Failed to convert params");
@@ -67,6 +143,49 @@ impl Context {
             .send(ServerMessage::Request(Call::MethodCall(call)));
     }
 
+    /// Cancel in-flight requests of the same method for the same buffer as request `id` has and
+    /// forget about them, so their responses are ignored.
+    fn cancel_superseded_requests(&mut self, id: &Id, method: &str) {
+        if !SUPERSEDED_METHODS.contains(&method) {
+            return;
+        }
+        let (buffile, has_position) = match self.response_waitlist.get(id) {
+            Some((meta, _, params)) => (meta.buffile.clone(), params.get("position").is_some()),
+            None => return,
+        };
+        // NOTE request made at cursor position is what user asked for, e.g. lsp-open-link, so
+        // automatic request of the same method without position doesn't replace it
+        let superseded = self
+            .response_waitlist
+            .iter()
+            .filter(|(x, (meta, x_method, x_params))| {
+                *x != id
+                    && x_method == method
+                    && meta.buffile == buffile
+                    && (has_position || x_params.get("position").is_none())
+            })
+            .map(|(x, _)| x.clone())
+            .collect::<Vec<_>>();
+        // NOTE server could have already moved past the result id the cached semantic tokens have,
+        // so the next request must be a full one
+        let is_semantic_tokens =
+            method == SEMANTIC_TOKENS_FULL_METHOD || method == SEMANTIC_TOKENS_FULL_DELTA_METHOD;
+        if is_semantic_tokens && !superseded.is_empty() {
+            if let Some(cache) = self.semantic_tokens.get_mut(&buffile) {
+                cache.result_id = None;
+            }
+        }
+        for x in superseded {
+            self.response_waitlist.remove(&x);
+            let id = match x {
+                Id::Num(n) => NumberOrString::Number(n),
+                Id::Str(s) => NumberOrString::String(s),
+                Id::Null => continue,
+            };
+            self.notify(notification::Cancel::METHOD.into(), CancelParams { id });
+        }
+    }
+
     pub fn notify(&mut self, method: String, params: impl ToParams) {
         let params = params.to_params();
         if params.is_err() {
@@ -86,13 +205,83 @@ impl Context {
             .send(ServerMessage::Request(Call::Notification(notification)));
     }
 
+    pub fn reply(&mut self, id: Id, result: Result<Value, jsonrpc_core::Error>) {
+        let output = Output::from(result, id, Some(Version::V2));
+        self.lang_srv_tx.send(ServerMessage::Response(output));
+    }
+
     pub fn exec(&self, meta: EditorMeta, command: String) {
         self.editor_tx.send(EditorResponse { meta, command });
     }
 
+    /// Check if buffer was changed since the request described by `meta` was made.
+    pub fn is_outdated(&self, meta: &EditorMeta) -> bool {
+        self.is_version_outdated(&meta.buffile, meta.version)
+    }
+
+    /// Check if buffer was changed since it had `version`.
+    pub fn is_version_outdated(&self, buffile: &str, version: u64) -> bool {
+        self.versions
+            .get(buffile)
+            .map(|x| *x > version)
+            .unwrap_or(false)
+    }
+
+    /// Tell user that edits computed for an outdated version of the buffer are not applied.
+    pub fn reject_outdated_edits(&self, meta: &EditorMeta, method: &str) {
+        let command = format!(
+            "lsp-show-error %§Buffer was changed since {} request was made, edits are discarded§",
+            method
+        );
+        self.exec(meta.clone(), command);
+    }
+
     pub fn next_request_id(&mut self) -> Id {
         let id = Id::Num(self.request_counter);
         self.request_counter += 1;
         id
     }
+
+    /// Start collecting responses for `size` requests made for `version` of the buffer which
+    /// should be handled together.
+    pub fn start_batch(&mut self, size: usize, version: u64) -> BatchId {
+        let batch_id = self.batch_counter;
+        self.batch_counter += 1;
+        self.batches.insert(
+            batch_id,
+            Batch {
+                size,
+                version,
+                responses: Vec::with_capacity(size),
+                failed: false,
+            },
+        );
+        batch_id
+    }
+
+    /// Mark the batch as missing some of responses, e.g. because request failed.
+    pub fn fail_batch(&mut self, batch_id: BatchId) {
+        if let Some(batch) = self.batches.get_mut(&batch_id) {
+            batch.failed = true;
+        }
+    }
+
+    /// Add response to the batch. Returns the batch when the last response arrives.
+    pub fn add_to_batch(&mut self, batch_id: BatchId, response: Value) -> Option<Batch> {
+        let complete = match self.batches.get_mut(&batch_id) {
+            Some(batch) => {
+                batch.responses.push(response);
+                batch.responses.len() == batch.size
+            }
+            None => {
+                error!("Batch {} is not found", batch_id);
+                return None;
+            }
+        };
+        if complete {
+            self.batches.remove(&batch_id)
+        } else {
+            None
+        }
+    }
 }
@@ -4,7 +4,7 @@ use diagnostics;
 use editor_transport;
 use fnv::FnvHashMap;
 use general;
-use jsonrpc_core::{Call, ErrorCode, Output, Params};
+use jsonrpc_core::{self, Call, ErrorCode, Id, Output, Params};
 use language_features::*;
 use language_server_transport;
 use languageserver_types::notification::Notification;
@@ -22,6 +22,7 @@ use std::time::Duration;
 use text_sync::*;
 use toml;
 use types::*;
+use workspace;
 
 type Controllers = FnvHashMap<Route, Sender<EditorRequest>>;
 
@@ -181,6 +182,12 @@ impl Controller {
                                     notification::DidSaveTextDocument::METHOD => (),
                                     // TODO if auto-hover is not enabled we might want warning about parking as well
                                     request::HoverRequest::METHOD => (),
+                                    request::DocumentHighlightRequest::METHOD => (),
+                                    request::CodeLensRequest::METHOD => (),
+                                    request::DocumentLinkRequest::METHOD => (),
+                                    DOCUMENT_COLOR_METHOD => (),
+                                    INLAY_HINT_METHOD => (),
+                                    request::ResolveCompletionItem::METHOD => (),
                                     _ => ctx.exec(msg.meta.clone(), "lsp-show-error 'Language server is not initialized, parking request'".to_string())
                                 }
                             }
@@ -211,7 +218,12 @@ impl Controller {
                             let mut ctx = ctx.lock().expect("Failed to lock context");
                             match call {
                                 Call::MethodCall(request) => {
-                                    debug!("Requests from language server are not supported yet: {:?}", request);
+                                    dispatch_server_request(
+                                        request.id,
+                                        &request.method,
+                                        request.params.unwrap_or(Params::None),
+                                        &mut ctx,
+                                    );
                                 }
                                 Call::Notification(notification) => {
                                     if notification.params.is_none() {
@@ -235,6 +247,11 @@ impl Controller {
                                 Output::Success(success) => {
                                     if let Some(request) = ctx.response_waitlist.remove(&success.id) {
                                         let (meta, method, params) = request;
+                                        if is_stale_response(&meta, &method, &ctx) {
+                                            reject_stale_response(&meta, &method, &ctx);
+                                            discard_response(&meta, &method, params, &mut ctx);
+                                            continue;
+                                        }
                                         dispatch_server_response(
                                             &meta,
                                             &method,
@@ -243,13 +260,14 @@ impl Controller {
                                             &mut ctx,
                                         );
                                     } else {
-                                        error!("Id {:?} is not in waitlist!", success.id);
+                                        // NOTE responses to cancelled requests are expected here
+                                        debug!("Id {:?} is not in waitlist", success.id);
                                     }
                                 }
                                 Output::Failure(failure) => {
-                                    error!("Error response from server: {:?}", failure);
                                     if let Some(request) = ctx.response_waitlist.remove(&failure.id) {
-                                        let (meta, method, _) = request;
+                                        error!("Error response from server: {:?}", failure);
+                                        let (meta, method, params) = request;
                                         let msg = match failure.error.code {
                                             ErrorCode::MethodNotFound => {
                                                 format!("{} language server doesn't support method {}", ctx.language_id, method)
@@ -258,9 +276,10 @@ impl Controller {
                                                 format!("{} language server error: {}", ctx.language_id, failure.error.message)
                                             }
                                         };
-                                        ctx.exec(meta, format!("lsp-show-error %§{}§", msg));
+                                        ctx.exec(meta.clone(), format!("lsp-show-error %§{}§", msg));
+                                        discard_response(&meta, &method, params, &mut ctx);
                                     } else {
-                                        error!("Id {:?} is not in waitlist!", failure.id);
+                                        debug!("Error response for request which is not in waitlist: {:?}", failure);
                                     }
                                 }
                             }
@@ -320,27 +339,114 @@ fn dispatch_editor_request(request: EditorRequest, mut ctx: &mut Context) {
         request::Completion::METHOD => {
             completion::text_document_completion(params, meta, &mut ctx);
         }
+        request::ResolveCompletionItem::METHOD => {
+            completion::completion_item_resolve(params, meta, &mut ctx);
+        }
+        "completionItem/accept" => {
+            completion::completion_item_accept(params, meta, &mut ctx);
+        }
         request::HoverRequest::METHOD => {
             hover::text_document_hover(params, meta, &mut ctx);
         }
         request::GotoDefinition::METHOD => {
             definition::text_document_definition(params, meta, &mut ctx);
         }
+        request::GotoImplementation::METHOD => {
+            definition::text_document_implementation(params, meta, &mut ctx);
+        }
+        request::GotoTypeDefinition::METHOD => {
+            definition::text_document_type_definition(params, meta, &mut ctx);
+        }
+        GOTO_DECLARATION_METHOD => {
+            definition::text_document_declaration(params, meta, &mut ctx);
+        }
         request::References::METHOD => {
             references::text_document_references(params, meta, &mut ctx);
         }
+        request::Rename::METHOD => {
+            rename::text_document_rename(params, meta, &mut ctx);
+        }
         notification::Exit::METHOD => {
             general::exit(params, meta, &mut ctx);
         }
         request::SignatureHelpRequest::METHOD => {
             signature_help::text_document_signature_help(params, meta, &mut ctx);
         }
+        request::DocumentHighlightRequest::METHOD => {
+            document_highlight::text_document_document_highlight(params, meta, &mut ctx);
+        }
         request::DocumentSymbol::METHOD => {
             document_symbol::text_document_document_symbol(params, meta, &mut ctx);
         }
+        request::WorkspaceSymbol::METHOD => {
+            workspace_symbol::workspace_symbol(params, meta, &mut ctx);
+        }
         request::Formatting::METHOD => {
             formatting::text_document_formatting(params, meta, &mut ctx);
         }
+        request::RangeFormatting::METHOD => {
+            formatting::text_document_range_formatting(params, meta, &mut ctx);
+        }
+        request::OnTypeFormatting::METHOD => {
+            formatting::text_document_on_type_formatting(params, meta, &mut ctx);
+        }
+        request::CodeActionRequest::METHOD => {
+            code_action::text_document_code_action(params, meta, &mut ctx);
+        }
+        "codeAction/select" => {
+            code_action::code_action_select(params, meta, &mut ctx);
+        }
+        request::CodeLensRequest::METHOD => {
+            code_lens::text_document_code_lens(params, meta, &mut ctx);
+        }
+        "codeLens/menu" => {
+            code_lens::code_lens_menu(params, meta, &mut ctx);
+        }
+        request::DocumentLinkRequest::METHOD => {
+            document_link::text_document_document_link(params, meta, &mut ctx);
+        }
+        "documentLink/open" => {
+            document_link::document_link_open(params, meta, &mut ctx);
+        }
+        FOLDING_RANGE_METHOD => {
+            folding_range::text_document_folding_range(params, meta, &mut ctx);
+        }
+        SELECTION_RANGE_METHOD => {
+            selection_range::text_document_selection_range(params, meta, &mut ctx);
+        }
+        "selectionRange/shrink" => {
+            selection_range::selection_range_shrink(params, meta, &mut ctx);
+        }
+        DOCUMENT_COLOR_METHOD => {
+            document_color::text_document_document_color(params, meta, &mut ctx);
+        }
+        COLOR_PRESENTATION_METHOD => {
+            document_color::text_document_color_presentation(params, meta, &mut ctx);
+        }
+        PREPARE_CALL_HIERARCHY_METHOD => {
+            call_hierarchy::text_document_prepare_call_hierarchy(params, meta, &mut ctx);
+        }
+        "callHierarchy/expand" => {
+            call_hierarchy::call_hierarchy_expand(params, meta, &mut ctx);
+        }
+        PREPARE_TYPE_HIERARCHY_METHOD => {
+            type_hierarchy::text_document_prepare_type_hierarchy(params, meta, &mut ctx);
+        }
+        "typeHierarchy/expand" => {
+            type_hierarchy::type_hierarchy_expand(params, meta, &mut ctx);
+        }
+        INLAY_HINT_METHOD => {
+            inlay_hint::text_document_inlay_hint(params, meta, &mut ctx);
+        }
+        "inlayHint/info" => {
+            inlay_hint::inlay_hint_info(params, meta, &mut ctx);
+        }
+        request::ExecuteCommand::METHOD => {
+            workspace::workspace_execute_command(params, meta, &mut ctx);
+        }
+        "workspace/applyEdit/result" => {
+            workspace::apply_edit_result(params, &mut ctx);
+        }
         "textDocument/diagnostics" => {
             diagnostics::editor_diagnostics(params, meta, &mut ctx);
         }
@@ -353,6 +459,29 @@ fn dispatch_editor_request(request: EditorRequest, mut ctx: &mut Context) {
     }
 }
 
+fn dispatch_server_request(id: Id, method: &str, params: Params, mut ctx: &mut Context) {
+    let response = match method {
+        request::ApplyWorkspaceEdit::METHOD => {
+            // NOTE reply is sent once editor applies edits
+            workspace::apply_edit_from_server(id, params, &mut ctx);
+            return;
+        }
+        // NOTE these requests only inform client, so it's enough to acknowledge them
+        "client/registerCapability"
+        | "client/unregisterCapability"
+        | "window/workDoneProgress/create" => Ok(Value::Null),
+        SEMANTIC_TOKENS_REFRESH_METHOD => {
+            semantic_tokens::semantic_tokens_refresh(params, &mut ctx)
+        }
+        INLAY_HINT_REFRESH_METHOD => inlay_hint::inlay_hint_refresh(params, &mut ctx),
+        _ => {
+            warn!("Unsupported method: {}", method);
+            Err(jsonrpc_core::Error::method_not_found())
+        }
+    };
+    ctx.reply(id, response);
+}
+
 fn dispatch_server_notification(method: &str, params: Params, mut ctx: &mut Context) {
     match method {
         notification::PublishDiagnostics::METHOD => {
@@ -383,6 +512,90 @@ fn dispatch_server_notification(method: &str, params: Params, mut ctx: &mut Cont
     }
 }
 
+/// Responses of these methods don't depend on the buffer version they were requested for: they are
+/// about items cached by kak-lsp or requested from other buffers, e.g. hierarchy ones.
+const VERSION_INDEPENDENT_METHODS: &[&str] = &[
+    request::Initialize::METHOD,
+    request::ExecuteCommand::METHOD,
+    request::ResolveCompletionItem::METHOD,
+    request::CodeLensResolve::METHOD,
+    request::DocumentLinkResolve::METHOD,
+    request::WorkspaceSymbol::METHOD,
+    INLAY_HINT_RESOLVE_METHOD,
+    INCOMING_CALLS_METHOD,
+    OUTGOING_CALLS_METHOD,
+    SUPERTYPES_METHOD,
+    SUBTYPES_METHOD,
+];
+
+/// Responses of these methods edit the buffer, applying them to a changed buffer corrupts it.
+/// Edits which are applied later, e.g. when code action is selected or completion is accepted, are
+/// checked against buffer version by their handlers.
+const EDIT_METHODS: &[&str] = &[
+    request::Formatting::METHOD,
+    request::RangeFormatting::METHOD,
+    request::OnTypeFormatting::METHOD,
+    request::Rename::METHOD,
+    COLOR_PRESENTATION_METHOD,
+];
+
+/// Requests of these methods are sent in batches whose responses are handled all at once.
+const BATCH_METHODS: &[&str] = &[
+    request::RangeFormatting::METHOD,
+    request::CodeLensResolve::METHOD,
+    INLAY_HINT_RESOLVE_METHOD,
+];
+
+/// Clean up after request which response is not going to be handled because request failed or
+/// response is stale.
+fn discard_response(meta: &EditorMeta, method: &str, params: EditorParams, ctx: &mut Context) {
+    if method == SEMANTIC_TOKENS_FULL_METHOD || method == SEMANTIC_TOKENS_FULL_DELTA_METHOD {
+        // NOTE result id server knows about is unknown now, so full tokens must be requested
+        if let Some(cache) = ctx.semantic_tokens.get_mut(&meta.buffile) {
+            cache.result_id = None;
+        }
+    }
+    complete_batch_request(meta, method, params, ctx);
+}
+
+/// Count failed or dropped request of the batch as done with empty result, otherwise the batch
+/// would wait for it forever. The batch is marked as failed to let its handler decide whether
+/// incomplete responses are still useful.
+fn complete_batch_request(
+    meta: &EditorMeta,
+    method: &str,
+    params: EditorParams,
+    ctx: &mut Context,
+) {
+    if !BATCH_METHODS.contains(&method) {
+        return;
+    }
+    // NOTE params of all batch methods carry batch id
+    match BatchParams::deserialize(params.clone()) {
+        Ok(batch_params) => ctx.fail_batch(batch_params.batch_id),
+        Err(_) => error!("Params of {} should carry batch id", method),
+    }
+    dispatch_server_response(meta, method, params, Value::Null, ctx);
+}
+
+/// Check if buffer was changed since the request was made.
+fn is_stale_response(meta: &EditorMeta, method: &str, ctx: &Context) -> bool {
+    if VERSION_INDEPENDENT_METHODS.contains(&method) {
+        return false;
+    }
+    ctx.is_outdated(meta)
+}
+
+/// Tell user about stale edits, while stale read-only results are just dropped. Stale member of a
+/// batch fails the whole batch, which is reported once by its handler.
+fn reject_stale_response(meta: &EditorMeta, method: &str, ctx: &Context) {
+    if EDIT_METHODS.contains(&method) && !BATCH_METHODS.contains(&method) {
+        ctx.reject_outdated_edits(meta, method);
+    } else {
+        debug!("Dropping stale {} response for {}", method, meta.buffile);
+    }
+}
+
 fn dispatch_server_response(
     meta: &EditorMeta,
     method: &str,
@@ -399,6 +612,15 @@ fn dispatch_server_response(
                 &mut ctx,
             );
         }
+        request::ResolveCompletionItem::METHOD => {
+            completion::editor_completion_item_resolve(
+                meta,
+                &CompletionItemIndexParams::deserialize(params).expect("Failed to parse params"),
+                serde_json::from_value(response)
+                    .expect("Failed to parse completion item resolve response"),
+                &mut ctx,
+            );
+        }
         request::HoverRequest::METHOD => {
             let response = if response.is_null() {
                 None
@@ -412,7 +634,10 @@ fn dispatch_server_response(
                 &mut ctx,
             );
         }
-        request::GotoDefinition::METHOD => {
+        request::GotoDefinition::METHOD
+        | request::GotoImplementation::METHOD
+        | request::GotoTypeDefinition::METHOD
+        | GOTO_DECLARATION_METHOD => {
             definition::editor_definition(
                 meta,
                 &PositionParams::deserialize(params).expect("Failed to parse params"),
@@ -428,6 +653,14 @@ fn dispatch_server_response(
                 &mut ctx,
             );
         }
+        request::Rename::METHOD => {
+            rename::editor_rename(
+                meta,
+                &TextDocumentRenameParams::deserialize(params).expect("Failed to parse params"),
+                serde_json::from_value(response).expect("Failed to parse rename response"),
+                &mut ctx,
+            );
+        }
         request::SignatureHelpRequest::METHOD => {
             signature_help::editor_signature_help(
                 meta,
@@ -436,6 +669,14 @@ fn dispatch_server_response(
                 &mut ctx,
             );
         }
+        request::DocumentHighlightRequest::METHOD => {
+            document_highlight::editor_document_highlight(
+                meta,
+                serde_json::from_value(response)
+                    .expect("Failed to parse document highlight response"),
+                &mut ctx,
+            );
+        }
         request::DocumentSymbol::METHOD => {
             document_symbol::editor_document_symbol(
                 meta,
@@ -443,6 +684,14 @@ fn dispatch_server_response(
                 &mut ctx,
             );
         }
+        request::WorkspaceSymbol::METHOD => {
+            workspace_symbol::editor_workspace_symbol(
+                meta,
+                serde_json::from_value(response)
+                    .expect("Failed to parse workspace symbol response"),
+                &mut ctx,
+            );
+        }
         request::Formatting::METHOD => {
             formatting::editor_formatting(
                 meta,
@@ -451,12 +700,201 @@ fn dispatch_server_response(
                 &mut ctx,
             );
         }
-        request::Initialize::METHOD => {
-            ctx.capabilities = Some(
-                serde_json::from_value::<InitializeResult>(response)
-                    .expect("Failed to parse initialized response")
-                    .capabilities,
+        request::RangeFormatting::METHOD => {
+            formatting::editor_range_formatting(
+                meta,
+                &BatchParams::deserialize(params).expect("Failed to parse params"),
+                response,
+                &mut ctx,
+            );
+        }
+        request::OnTypeFormatting::METHOD => {
+            formatting::editor_on_type_formatting(
+                meta,
+                serde_json::from_value(response)
+                    .expect("Failed to parse on type formatting response"),
+                &mut ctx,
             );
+        }
+        request::CodeActionRequest::METHOD => {
+            code_action::editor_code_action(
+                meta,
+                serde_json::from_value(response).expect("Failed to parse code action response"),
+                &mut ctx,
+            );
+        }
+        request::CodeLensRequest::METHOD => {
+            code_lens::editor_code_lens(
+                meta,
+                params,
+                serde_json::from_value(response).expect("Failed to parse code lens response"),
+                &mut ctx,
+            );
+        }
+        request::CodeLensResolve::METHOD => {
+            code_lens::editor_code_lens_resolve(
+                meta,
+                &CodeLensResolveParams::deserialize(params).expect("Failed to parse params"),
+                response,
+                &mut ctx,
+            );
+        }
+        request::DocumentLinkRequest::METHOD => {
+            document_link::editor_document_link(
+                meta,
+                params,
+                serde_json::from_value(response).expect("Failed to parse document link response"),
+                &mut ctx,
+            );
+        }
+        request::DocumentLinkResolve::METHOD => {
+            document_link::editor_document_link_resolve(
+                meta,
+                serde_json::from_value(response)
+                    .expect("Failed to parse document link resolve response"),
+                &mut ctx,
+            );
+        }
+        FOLDING_RANGE_METHOD => {
+            folding_range::editor_folding_range(
+                meta,
+                &TextDocumentFoldingRangeParams::deserialize(params)
+                    .expect("Failed to parse params"),
+                serde_json::from_value(response).expect("Failed to parse folding range response"),
+                &mut ctx,
+            );
+        }
+        SELECTION_RANGE_METHOD => {
+            selection_range::editor_selection_range(
+                meta,
+                params,
+                serde_json::from_value(response).expect("Failed to parse selection range response"),
+                &mut ctx,
+            );
+        }
+        DOCUMENT_COLOR_METHOD => {
+            document_color::editor_document_color(
+                meta,
+                params,
+                serde_json::from_value(response).expect("Failed to parse document color response"),
+                &mut ctx,
+            );
+        }
+        COLOR_PRESENTATION_METHOD => {
+            document_color::editor_color_presentation(
+                meta,
+                &TextDocumentColorPresentationParams::deserialize(params)
+                    .expect("Failed to parse params"),
+                serde_json::from_value(response)
+                    .expect("Failed to parse color presentation response"),
+                &mut ctx,
+            );
+        }
+        PREPARE_CALL_HIERARCHY_METHOD => {
+            call_hierarchy::editor_prepare_call_hierarchy(
+                meta,
+                &TextDocumentCallHierarchyParams::deserialize(params)
+                    .expect("Failed to parse params"),
+                serde_json::from_value(response)
+                    .expect("Failed to parse prepare call hierarchy response"),
+                &mut ctx,
+            );
+        }
+        INCOMING_CALLS_METHOD | OUTGOING_CALLS_METHOD => {
+            call_hierarchy::editor_calls(
+                meta,
+                method,
+                &HierarchyNodeParams::deserialize(params).expect("Failed to parse params"),
+                response,
+                &mut ctx,
+            );
+        }
+        PREPARE_TYPE_HIERARCHY_METHOD => {
+            type_hierarchy::editor_prepare_type_hierarchy(
+                meta,
+                &TextDocumentTypeHierarchyParams::deserialize(params)
+                    .expect("Failed to parse params"),
+                serde_json::from_value(response)
+                    .expect("Failed to parse prepare type hierarchy response"),
+                &mut ctx,
+            );
+        }
+        SUPERTYPES_METHOD | SUBTYPES_METHOD => {
+            type_hierarchy::editor_types(
+                meta,
+                &HierarchyNodeParams::deserialize(params).expect("Failed to parse params"),
+                serde_json::from_value(response).expect("Failed to parse type hierarchy response"),
+                &mut ctx,
+            );
+        }
+        SEMANTIC_TOKENS_FULL_METHOD => {
+            semantic_tokens::editor_semantic_tokens_full(
+                meta,
+                serde_json::from_value(response).expect("Failed to parse semantic tokens response"),
+                &mut ctx,
+            );
+        }
+        SEMANTIC_TOKENS_FULL_DELTA_METHOD => {
+            semantic_tokens::editor_semantic_tokens_full_delta(
+                meta,
+                serde_json::from_value(response)
+                    .expect("Failed to parse semantic tokens delta response"),
+                &mut ctx,
+            );
+        }
+        SEMANTIC_TOKENS_RANGE_METHOD => {
+            semantic_tokens::editor_semantic_tokens_range(
+                meta,
+                serde_json::from_value(response).expect("Failed to parse semantic tokens response"),
+                &mut ctx,
+            );
+        }
+        INLAY_HINT_METHOD => {
+            inlay_hint::editor_inlay_hint(
+                meta,
+                Range::deserialize(params).expect("Failed to parse params"),
+                serde_json::from_value(response).expect("Failed to parse inlay hint response"),
+                &mut ctx,
+            );
+        }
+        INLAY_HINT_RESOLVE_METHOD => {
+            inlay_hint::editor_inlay_hint_resolve(
+                meta,
+                &InlayHintResolveParams::deserialize(params).expect("Failed to parse params"),
+                response,
+                &mut ctx,
+            );
+        }
+        request::ExecuteCommand::METHOD => {
+            // NOTE server applies effects of the command on its own,
+            // e.g. by sending workspace/applyEdit request
+        }
+        request::Initialize::METHOD => {
+            ctx.extended_capabilities = response
+                .get("capabilities")
+                .cloned()
+                .and_then(|capabilities| match serde_json::from_value(capabilities) {
+                    Ok(capabilities) => Some(capabilities),
+                    Err(e) => {
+                        error!("Failed to parse server capabilities: {}", e);
+                        None
+                    }
+                })
+                .unwrap_or_default();
+            // NOTE clangd announces encoding with offsetEncoding extension which predates
+            // positionEncoding capability
+            let offset_encoding = response
+                .get("offsetEncoding")
+                .and_then(|x| x.as_str())
+                .map(|x| x.to_string());
+            ctx.position_encoding = ctx
+                .extended_capabilities
+                .position_encoding
+                .clone()
+                .or(offset_encoding)
+                .and_then(|x| PositionEncoding::from_name(&x))
+                .unwrap_or_default();
+            ctx.capabilities = Some(parse_server_capabilities(response));
             let mut requests = Vec::with_capacity(ctx.pending_requests.len());
             for msg in ctx.pending_requests.drain(..) {
                 requests.push(msg);
@@ -472,6 +910,37 @@ fn dispatch_server_response(
     }
 }
 
+/// Parse capabilities from initialize response. languageserver-types doesn't know about options
+/// which newer servers reply with instead of flags, so capabilities it fails to parse are dropped
+/// and their support is determined from ExtendedServerCapabilities instead.
+fn parse_server_capabilities(response: Value) -> ServerCapabilities {
+    let capabilities = response.get("capabilities").cloned();
+    match serde_json::from_value::<InitializeResult>(response) {
+        Ok(result) => result.capabilities,
+        Err(e) => {
+            error!("Failed to parse initialize response: {}", e);
+            let capabilities = match capabilities {
+                Some(Value::Object(capabilities)) => capabilities,
+                _ => return ServerCapabilities::default(),
+            };
+            let capabilities = capabilities
+                .into_iter()
+                .filter(|(name, value)| {
+                    let mut capability = serde_json::Map::new();
+                    capability.insert(name.clone(), value.clone());
+                    let parsed =
+                        serde_json::from_value::<ServerCapabilities>(Value::Object(capability));
+                    if parsed.is_err() {
+                        warn!("Ignoring unexpected server capability {}: {}", name, value);
+                    }
+                    parsed.is_ok()
+                })
+                .collect();
+            serde_json::from_value(Value::Object(capabilities)).unwrap_or_default()
+        }
+    }
+}
+
 fn language_id_to_server_cmd(config: &Config, language_id: &str) -> Option<(String, Vec<String>)> {
     if let Some(language) = config.language.get(language_id) {
         return Some((language.command.clone(), language.args.clone()));
@@ -1,7 +1,9 @@
 use context::*;
 use languageserver_types::*;
+use position::PositionConverter;
 use std::path::Path;
 use types::*;
+use util::lsp_range_to_kakoune;
 
 pub fn publish_diagnostics(params: PublishDiagnosticsParams, ctx: &mut Context) {
     let session = ctx.session.clone();
@@ -15,29 +17,15 @@ pub fn publish_diagnostics(params: PublishDiagnosticsParams, ctx: &mut Context)
         return;
     }
     let version = *version.unwrap();
+    let converter = PositionConverter::new(buffile, ctx);
     let ranges = ctx.diagnostics
         .get(buffile)
         .unwrap()
         .iter()
         .map(|x| {
-            // LSP ranges are 0-based, but Kakoune's 1-based.
-            // LSP ranges are exclusive, but Kakoune's are inclusive.
-            // Also from LSP spec: If you want to specify a range that contains a line including
-            // the line ending character(s) then use an end position denoting the start of the next
-            // line.
-            let mut end_line = x.range.end.line;
-            let mut end_char = x.range.end.character;
-            if end_char > 0 {
-                end_line += 1;
-            } else {
-                end_char = 1_000_000;
-            }
             format!(
-                "{}.{},{}.{}|{}",
-                x.range.start.line + 1,
-                x.range.start.character + 1,
-                end_line,
-                end_char,
+                "{}|{}",
+                lsp_range_to_kakoune(&converter.range_to_kakoune(x.range)),
                 match x.severity {
                     Some(DiagnosticSeverity::Error) => "DiagnosticError",
                     _ => "DiagnosticWarning",
@@ -52,7 +40,7 @@ pub fn publish_diagnostics(params: PublishDiagnosticsParams, ctx: &mut Context)
         .unwrap()
         .iter()
         .map(|x| {
-            // See above
+            // LSP lines are 0-based, but Kakoune's 1-based
             format!(
                 "{}|{}",
                 x.range.start.line + 1,
@@ -84,9 +72,11 @@ pub fn editor_diagnostics(_params: EditorParams, meta: &EditorMeta, ctx: &mut Co
     let content = ctx.diagnostics
         .iter()
         .flat_map(|(filename, diagnostics)| {
+            let converter = PositionConverter::new(filename, ctx);
             diagnostics
                 .iter()
                 .map(|x| {
+                    let start = converter.to_kakoune(x.range.start);
                     format!(
                         "{}:{}:{}: {}:{}",
                         Path::new(filename)
@@ -95,8 +85,8 @@ pub fn editor_diagnostics(_params: EditorParams, meta: &EditorMeta, ctx: &mut Co
                             .and_then(|p| Some(p.to_str().unwrap()))
                             .or_else(|| Some(filename))
                             .unwrap(),
-                        x.range.start.line + 1,
-                        x.range.start.character + 1,
+                        start.line + 1,
+                        start.character + 1,
                         match x.severity {
                             Some(DiagnosticSeverity::Error) => "error",
                             _ => "warning",
@@ -2,6 +2,7 @@ use context::*;
 use languageserver_types::notification::Notification;
 use languageserver_types::request::Request;
 use languageserver_types::*;
+use serde_json;
 use std::process;
 use toml;
 use types::*;
@@ -10,15 +11,24 @@ use url::Url;
 pub fn initialize(root_path: &str, meta: &EditorMeta, ctx: &mut Context) {
     let params = InitializeParams {
         capabilities: ClientCapabilities {
-            workspace: Some(WorkspaceClientCapabilities::default()),
+            workspace: Some(WorkspaceClientCapabilities {
+                apply_edit: Some(true),
+                workspace_edit: Some(WorkspaceEditCapability {
+                    document_changes: Some(true),
+                }),
+                execute_command: Some(GenericCapability::default()),
+                ..WorkspaceClientCapabilities::default()
+            }),
             text_document: Some(TextDocumentClientCapabilities {
                 completion: Some(CompletionCapability {
                     completion_item: Some(CompletionItemCapability {
                         documentation_format: Some(vec![MarkupKind::PlainText]),
+                        snippet_support: Some(true),
                         ..CompletionItemCapability::default()
                     }),
                     ..CompletionCapability::default()
                 }),
+                document_link: Some(GenericCapability::default()),
                 ..TextDocumentClientCapabilities::default()
             }),
             experimental: None,
@@ -29,6 +39,108 @@ pub fn initialize(root_path: &str, meta: &EditorMeta, ctx: &mut Context) {
         root_path: Some(root_path.to_string()),
         trace: Some(TraceOption::Off),
     };
+    // NOTE languageserver-types lacks some of newer client capabilities, so we patch them in
+    let mut params = serde_json::to_value(params).expect("Failed to serialize initialize params");
+    params["capabilities"]["textDocument"]["codeAction"] = json!({
+        "codeActionLiteralSupport": {
+            "codeActionKind": {
+                "valueSet": [
+                    "",
+                    "quickfix",
+                    "refactor",
+                    "refactor.extract",
+                    "refactor.inline",
+                    "refactor.rewrite",
+                    "source",
+                    "source.organizeImports"
+                ]
+            }
+        }
+    });
+    // NOTE Kakoune columns are bytes, so UTF-8 saves conversions when server supports it
+    params["capabilities"]["general"] = json!({
+        "positionEncodings": ["utf-8", "utf-16"]
+    });
+    params["capabilities"]["offsetEncoding"] = json!(["utf-8", "utf-16"]);
+    params["capabilities"]["textDocument"]["completion"]["completionItem"]["resolveSupport"] = json!({
+        "properties": ["documentation", "detail", "additionalTextEdits"]
+    });
+    params["capabilities"]["textDocument"]["foldingRange"] = json!({
+        "lineFoldingOnly": false
+    });
+    params["capabilities"]["textDocument"]["selectionRange"] = json!({
+        "dynamicRegistration": false
+    });
+    params["capabilities"]["textDocument"]["colorProvider"] = json!({
+        "dynamicRegistration": false
+    });
+    params["capabilities"]["textDocument"]["callHierarchy"] = json!({
+        "dynamicRegistration": false
+    });
+    params["capabilities"]["textDocument"]["typeHierarchy"] = json!({
+        "dynamicRegistration": false
+    });
+    params["capabilities"]["textDocument"]["semanticTokens"] = json!({
+        "dynamicRegistration": false,
+        "requests": {
+            "range": true,
+            "full": {
+                "delta": true
+            }
+        },
+        "tokenTypes": [
+            "namespace",
+            "type",
+            "class",
+            "enum",
+            "interface",
+            "struct",
+            "typeParameter",
+            "parameter",
+            "variable",
+            "property",
+            "enumMember",
+            "event",
+            "function",
+            "method",
+            "macro",
+            "keyword",
+            "modifier",
+            "comment",
+            "string",
+            "number",
+            "regexp",
+            "operator",
+            "decorator"
+        ],
+        "tokenModifiers": [
+            "declaration",
+            "definition",
+            "readonly",
+            "static",
+            "deprecated",
+            "abstract",
+            "async",
+            "modification",
+            "documentation",
+            "defaultLibrary"
+        ],
+        "formats": ["relative"],
+        "overlappingTokenSupport": false,
+        "multilineTokenSupport": false
+    });
+    params["capabilities"]["workspace"]["semanticTokens"] = json!({
+        "refreshSupport": true
+    });
+    params["capabilities"]["textDocument"]["inlayHint"] = json!({
+        "dynamicRegistration": false,
+        "resolveSupport": {
+            "properties": ["tooltip", "label.tooltip"]
+        }
+    });
+    params["capabilities"]["workspace"]["inlayHint"] = json!({
+        "refreshSupport": true
+    });
 
     let id = ctx.next_request_id();
     ctx.response_waitlist.insert(
@@ -66,14 +178,49 @@ pub fn capabilities(_params: EditorParams, meta: &EditorMeta, ctx: &mut Context)
         features.push("lsp-completion (hooked on InsertIdle)");
     }
 
+    if server_capabilities
+        .completion_provider
+        .as_ref()
+        .and_then(|x| x.resolve_provider)
+        .unwrap_or(false)
+    {
+        features.push("lsp-completion-item-resolve (documentation of selected item)");
+    }
+
     if server_capabilities.definition_provider.unwrap_or(false) {
         features.push("lsp-definition (mapped to `gd` by default)");
     }
 
+    let extended_capabilities = &ctx.extended_capabilities;
+
+    if extended_capabilities.implementation_provider.is_supported() {
+        features.push("lsp-implementation (mapped to `gi` by default)");
+    }
+
+    if extended_capabilities.type_definition_provider.is_supported() {
+        features.push("lsp-type-definition (mapped to `gy` by default)");
+    }
+
+    if extended_capabilities.declaration_provider.is_supported() {
+        features.push("lsp-declaration (mapped to `gD` by default)");
+    }
+
     if server_capabilities.references_provider.unwrap_or(false) {
         features.push("lsp-references");
     }
 
+    if server_capabilities
+        .document_highlight_provider
+        .unwrap_or(false)
+    {
+        features.push("lsp-document-highlight");
+    }
+
+    if server_capabilities.workspace_symbol_provider.unwrap_or(false) {
+        features.push("lsp-workspace-symbol");
+        features.push("lsp-workspace-symbol-incr");
+    }
+
     if server_capabilities
         .document_formatting_provider
         .unwrap_or(false)
@@ -81,6 +228,74 @@ pub fn capabilities(_params: EditorParams, meta: &EditorMeta, ctx: &mut Context)
         features.push("lsp-formatting");
     }
 
+    if server_capabilities
+        .document_range_formatting_provider
+        .unwrap_or(false)
+    {
+        features.push("lsp-range-formatting");
+    }
+
+    if server_capabilities
+        .document_on_type_formatting_provider
+        .is_some()
+    {
+        features.push("lsp-on-type-formatting (hooked on InsertChar)");
+    }
+
+    if ctx.extended_capabilities.code_action_provider.is_supported() {
+        features.push("lsp-code-actions");
+    }
+
+    if server_capabilities.code_lens_provider.is_some() {
+        features.push("lsp-code-lens (lenses are refreshed on buffer open and write)");
+    }
+
+    if ctx.extended_capabilities.document_link_provider.is_some() {
+        features.push("lsp-open-link");
+    }
+
+    if ctx.extended_capabilities.call_hierarchy_provider.is_supported() {
+        features.push("lsp-incoming-calls, lsp-outgoing-calls, lsp-call-hierarchy-expand");
+    }
+
+    if ctx.extended_capabilities.color_provider.is_supported() {
+        features.push("lsp-color-presentation-next (colors are refreshed on buffer open and write)");
+    }
+
+    if ctx.extended_capabilities.folding_range_provider.is_supported() {
+        features.push("lsp-folding-range-select, lsp-folding-range-select-kind, lsp-folding-ranges");
+    }
+
+    if ctx.extended_capabilities.selection_range_provider.is_supported() {
+        features.push("lsp-selection-range-expand, lsp-selection-range-shrink");
+    }
+
+    if ctx.extended_capabilities.type_hierarchy_provider.is_supported() {
+        features.push("lsp-supertypes, lsp-subtypes, lsp-type-hierarchy-expand");
+    }
+
+    if ctx.extended_capabilities.semantic_tokens_provider.is_some() {
+        features.push("semantic tokens highlighting (faces are configured in kak-lsp.toml)");
+    }
+
+    if ctx
+        .extended_capabilities
+        .inlay_hint_provider
+        .as_ref()
+        .map(|x| x.is_supported())
+        .unwrap_or(false)
+    {
+        features.push("lsp-inlay-hints-enable, lsp-inlay-hints-disable, lsp-inlay-hint-info");
+    }
+
+    if server_capabilities.rename_provider.unwrap_or(false) {
+        features.push("lsp-rename");
+    }
+
+    if server_capabilities.execute_command_provider.is_some() {
+        features.push("lsp-execute-command");
+    }
+
     features.push("lsp-diagnostics");
 
     let command = format!(
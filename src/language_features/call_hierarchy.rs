@@ -0,0 +1,63 @@
+use context::*;
+use language_features::hierarchy::{self, HierarchyKind};
+use serde::Deserialize;
+use serde_json::{self, Value};
+use types::*;
+
+pub fn text_document_prepare_call_hierarchy(
+    params: EditorParams,
+    meta: &EditorMeta,
+    ctx: &mut Context,
+) {
+    let call_hierarchy_params = TextDocumentCallHierarchyParams::deserialize(params.clone());
+    if call_hierarchy_params.is_err() {
+        error!("Params should follow TextDocumentCallHierarchyParams structure");
+        return;
+    }
+    let position = call_hierarchy_params.unwrap().position;
+    hierarchy::text_document_prepare_hierarchy(HierarchyKind::Call, position, params, meta, ctx);
+}
+
+pub fn editor_prepare_call_hierarchy(
+    meta: &EditorMeta,
+    params: &TextDocumentCallHierarchyParams,
+    result: Option<Vec<HierarchyItem>>,
+    ctx: &mut Context,
+) {
+    let method = if params.incoming {
+        INCOMING_CALLS_METHOD
+    } else {
+        OUTGOING_CALLS_METHOD
+    };
+    hierarchy::editor_prepare_hierarchy(HierarchyKind::Call, method, meta, result, ctx);
+}
+
+/// Request calls of the node on the given line of the call hierarchy buffer.
+pub fn call_hierarchy_expand(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    hierarchy::hierarchy_expand(HierarchyKind::Call, params, meta, ctx);
+}
+
+pub fn editor_calls(
+    meta: &EditorMeta,
+    method: &str,
+    params: &HierarchyNodeParams,
+    result: Value,
+    ctx: &mut Context,
+) {
+    let items = if method == INCOMING_CALLS_METHOD {
+        serde_json::from_value::<Option<Vec<CallHierarchyIncomingCall>>>(result)
+            .expect("Failed to parse incoming calls response")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|x| x.from)
+            .collect::<Vec<_>>()
+    } else {
+        serde_json::from_value::<Option<Vec<CallHierarchyOutgoingCall>>>(result)
+            .expect("Failed to parse outgoing calls response")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|x| x.to)
+            .collect::<Vec<_>>()
+    };
+    hierarchy::editor_hierarchy_children(HierarchyKind::Call, meta, params, items, ctx);
+}
@@ -0,0 +1,136 @@
+use context::*;
+use languageserver_types::request::Request;
+use languageserver_types::*;
+use position::PositionConverter;
+use serde::Deserialize;
+use types::*;
+use url::Url;
+use util::parse_kakoune_range;
+use workspace;
+
+pub fn text_document_code_action(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    if !ctx.extended_capabilities.code_action_provider.is_supported() {
+        ctx.exec(
+            meta.clone(),
+            "lsp-show-error 'Language server does not support code actions'".to_string(),
+        );
+        return;
+    }
+    let req_params = TextDocumentCodeActionParams::deserialize(params.clone());
+    if req_params.is_err() {
+        error!("Params should follow TextDocumentCodeActionParams structure");
+        return;
+    }
+    let req_params = req_params.unwrap();
+    let range = parse_kakoune_range(&req_params.selection_desc);
+    if range.is_none() {
+        error!("Failed to parse selection {}", req_params.selection_desc);
+        return;
+    }
+    let range = PositionConverter::new(&meta.buffile, ctx).range_to_lsp(range.unwrap());
+    // NOTE diagnostics overlapping with selection are sent to let server provide quick fixes
+    let diagnostics = ctx
+        .diagnostics
+        .get(&meta.buffile)
+        .map(|diagnostics| {
+            diagnostics
+                .iter()
+                .filter(|x| x.range.start <= range.end && range.start <= x.range.end)
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+    let req_params = CodeActionParams {
+        text_document: TextDocumentIdentifier {
+            uri: Url::from_file_path(&meta.buffile).unwrap(),
+        },
+        range,
+        context: CodeActionContext { diagnostics },
+    };
+    let id = ctx.next_request_id();
+    ctx.response_waitlist.insert(
+        id.clone(),
+        (meta.clone(), request::CodeActionRequest::METHOD.into(), params),
+    );
+    ctx.call(id, request::CodeActionRequest::METHOD.into(), req_params);
+}
+
+fn escape(s: &str) -> String {
+    s.replace("'", "''")
+}
+
+pub fn editor_code_action(
+    meta: &EditorMeta,
+    result: Option<Vec<CodeActionOrCommand>>,
+    ctx: &mut Context,
+) {
+    let actions = result.unwrap_or_default();
+    if actions.is_empty() {
+        ctx.code_actions.remove(&meta.buffile);
+        ctx.exec(meta.clone(), "info 'No code actions available'".to_string());
+        return;
+    }
+    let items = actions
+        .iter()
+        .enumerate()
+        .map(|(i, action)| {
+            let title = match action {
+                CodeActionOrCommand::Command(command) => &command.title,
+                CodeActionOrCommand::CodeAction(action) => &action.title,
+            };
+            format!("'{}' 'lsp-code-action-select {}'", escape(title), i)
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    ctx.code_actions
+        .insert(meta.buffile.clone(), (meta.version, actions));
+    ctx.exec(meta.clone(), format!("menu {}", items));
+}
+
+pub fn code_action_select(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    let params = CodeActionSelectParams::deserialize(params);
+    if params.is_err() {
+        error!("Params should follow CodeActionSelectParams structure");
+        return;
+    }
+    let index = params.unwrap().index;
+    let action = ctx
+        .code_actions
+        .get(&meta.buffile)
+        .and_then(|(version, actions)| {
+            actions
+                .get(index)
+                .map(|action| (*version, action.clone()))
+        });
+    if action.is_none() {
+        error!("Code action {} is not available for {}", index, meta.buffile);
+        return;
+    }
+    let (version, action) = action.unwrap();
+    // NOTE edits are computed for the buffer as it was when code actions were requested
+    if let CodeActionOrCommand::CodeAction(CodeAction { edit: Some(_), .. }) = action {
+        if meta.version > version {
+            ctx.reject_outdated_edits(meta, request::CodeActionRequest::METHOD);
+            return;
+        }
+    }
+    match action {
+        CodeActionOrCommand::Command(command) => {
+            workspace::execute_command(meta, command, ctx);
+        }
+        CodeActionOrCommand::CodeAction(action) => {
+            // NOTE edit is applied before command is executed according to the spec
+            if let Some(edit) = action.edit {
+                if !workspace::apply_edit(meta, edit, ctx) {
+                    ctx.exec(
+                        meta.clone(),
+                        "lsp-show-error 'Failed to apply some of code action edits'".to_string(),
+                    );
+                }
+            }
+            if let Some(command) = action.command {
+                workspace::execute_command(meta, command, ctx);
+            }
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use context::*;
+use languageserver_types::request::Request;
+use languageserver_types::*;
+use serde::Deserialize;
+use serde_json::{self, Value};
+use toml;
+use types::*;
+use url::Url;
+
+pub fn text_document_code_lens(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    // NOTE lenses are requested automatically, so don't bother user if server doesn't support them
+    if ctx
+        .capabilities
+        .as_ref()
+        .and_then(|x| x.code_lens_provider.as_ref())
+        .is_none()
+    {
+        return;
+    }
+    request_code_lenses(params, meta, ctx);
+}
+
+fn request_code_lenses(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    let req_params = CodeLensParams {
+        text_document: TextDocumentIdentifier {
+            uri: Url::from_file_path(&meta.buffile).unwrap(),
+        },
+    };
+    let id = ctx.next_request_id();
+    ctx.response_waitlist.insert(
+        id.clone(),
+        (meta.clone(), request::CodeLensRequest::METHOD.into(), params),
+    );
+    ctx.call(id, request::CodeLensRequest::METHOD.into(), req_params);
+}
+
+pub fn editor_code_lens(
+    meta: &EditorMeta,
+    params: EditorParams,
+    result: Option<Vec<CodeLens>>,
+    ctx: &mut Context,
+) {
+    let lenses = result.unwrap_or_default();
+    // NOTE there could be several lenses on the same line, but we want only one flag per line
+    let mut lines = lenses
+        .iter()
+        .map(|x| x.range.start.line + 1)
+        .collect::<Vec<_>>();
+    lines.sort_unstable();
+    lines.dedup();
+    let line_flags = lines
+        .into_iter()
+        .map(|line| format!("{}|%opt[lsp_code_lens_sign]", line))
+        .collect::<Vec<String>>()
+        .join(" ");
+    ctx.code_lenses
+        .insert(meta.buffile.clone(), (meta.version, lenses));
+    // NOTE wrap it in another eval and quotes, to make sure the %opt[] tags are expanded
+    let command = format!(
+        "eval -buffer %§{}§ %§eval \"set buffer lsp_code_lenses {} {}\"§",
+        meta.buffile, meta.version, line_flags
+    );
+    ctx.exec(meta.clone(), command);
+    // NOTE lenses are requested with position only when user wants to see the menu
+    if let Ok(params) = PositionParams::deserialize(params) {
+        code_lens_menu_at(meta, params.position.line, ctx);
+    }
+}
+
+pub fn code_lens_menu(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    let position = PositionParams::deserialize(params.clone());
+    if position.is_err() {
+        error!("Params should follow PositionParams structure");
+        return;
+    }
+    let line = position.unwrap().position.line;
+    // NOTE lenses are refreshed only when buffer is opened or saved, so they are requested again
+    // if the buffer was changed since then, otherwise their lines could point to the wrong place
+    let is_cached = ctx
+        .code_lenses
+        .get(&meta.buffile)
+        .map(|(version, _)| *version == meta.version)
+        .unwrap_or(false);
+    let is_supported = ctx
+        .capabilities
+        .as_ref()
+        .and_then(|x| x.code_lens_provider.as_ref())
+        .is_some();
+    if is_cached || !is_supported {
+        code_lens_menu_at(meta, line, ctx);
+    } else {
+        request_code_lenses(params, meta, ctx);
+    }
+}
+
+fn code_lens_menu_at(meta: &EditorMeta, line: u64, ctx: &mut Context) {
+    let resolve_provider = ctx
+        .capabilities
+        .as_ref()
+        .and_then(|x| x.code_lens_provider.as_ref())
+        .and_then(|x| x.resolve_provider)
+        .unwrap_or(false);
+    let unresolved = ctx
+        .code_lenses
+        .get(&meta.buffile)
+        .map(|(_, lenses)| {
+            lenses
+                .iter()
+                .filter(|x| x.range.start.line == line && x.command.is_none())
+                .map(|x| serde_json::to_value(x).unwrap())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if !resolve_provider || unresolved.is_empty() {
+        show_code_lens_menu(meta, line, ctx);
+        return;
+    }
+    let batch_id = ctx.start_batch(unresolved.len(), meta.version);
+    let batch_params = toml::Value::try_from(CodeLensResolveParams { batch_id, line }).unwrap();
+    for lens in unresolved {
+        let id = ctx.next_request_id();
+        ctx.response_waitlist.insert(
+            id.clone(),
+            (
+                meta.clone(),
+                request::CodeLensResolve::METHOD.into(),
+                batch_params.clone(),
+            ),
+        );
+        ctx.call(id, request::CodeLensResolve::METHOD.into(), lens);
+    }
+}
+
+pub fn editor_code_lens_resolve(
+    meta: &EditorMeta,
+    params: &CodeLensResolveParams,
+    result: Value,
+    ctx: &mut Context,
+) {
+    if let Some(batch) = ctx.add_to_batch(params.batch_id, result) {
+        let resolved = batch
+            .responses
+            .into_iter()
+            .filter_map(|result| serde_json::from_value::<CodeLens>(result).ok())
+            .collect::<Vec<_>>();
+        if let Some((_, lenses)) = ctx.code_lenses.get_mut(&meta.buffile) {
+            // NOTE responses could arrive in any order, so we replace all unresolved lenses on the
+            // line rather than tracking each of them
+            lenses.retain(|x| x.range.start.line != params.line || x.command.is_some());
+            lenses.extend(resolved);
+        }
+        show_code_lens_menu(meta, params.line, ctx);
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace("'", "''")
+}
+
+fn show_code_lens_menu(meta: &EditorMeta, line: u64, ctx: &mut Context) {
+    // NOTE command is put into menu entry itself rather than referenced by index, because lenses
+    // could be refreshed before the entry is selected
+    let items = ctx
+        .code_lenses
+        .get(&meta.buffile)
+        .map(|(_, lenses)| {
+            lenses
+                .iter()
+                .filter(|x| x.range.start.line == line)
+                .filter_map(|x| {
+                    x.command.as_ref().map(|command| {
+                        let arguments = command.arguments.clone().unwrap_or_default();
+                        let execute = format!(
+                            "lsp-execute-command '{}' '{}'",
+                            escape(&command.command),
+                            escape(&serde_json::to_string(&arguments).unwrap())
+                        );
+                        format!("'{}' '{}'", escape(&command.title), escape(&execute))
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if items.is_empty() {
+        ctx.exec(meta.clone(), "info 'No code lenses on this line'".to_string());
+        return;
+    }
+    ctx.exec(meta.clone(), format!("menu {}", items.join(" ")));
+}
@@ -1,11 +1,17 @@
 use context::*;
 use languageserver_types::request::Request;
 use languageserver_types::*;
+use position::PositionConverter;
 use regex::Regex;
 use serde::Deserialize;
+use serde_json;
+use snippet::{self, Snippet};
 use std;
+use text_edit::apply_text_edits;
+use toml;
 use types::*;
 use url::Url;
+use workspace;
 
 pub fn text_document_completion(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
     let req_params = TextDocumentCompletionParams::deserialize(params.clone());
@@ -14,7 +20,7 @@ pub fn text_document_completion(params: EditorParams, meta: &EditorMeta, ctx: &m
         return;
     }
     let req_params = req_params.unwrap();
-    let position = req_params.position;
+    let position = PositionConverter::new(&meta.buffile, ctx).to_lsp(req_params.position);
 
     let req_params = CompletionParams {
         text_document: TextDocumentIdentifier {
@@ -41,30 +47,13 @@ pub fn editor_completion(
         CompletionResponse::Array(items) => items,
         CompletionResponse::List(list) => list.items,
     };
-    let unescape_markdown_re = Regex::new(r"\\(?P<c>.)").unwrap();
     let maxlen = items.iter().map(|x| x.label.len()).max().unwrap_or(0);
     let escape = |s: &str| s.replace("'", "''").replace("|", r"\|");
 
-    let items = items
-        .into_iter()
+    let formatted_items = items
+        .iter()
         .map(|x| {
-            let mut doc: String = match &x.documentation {
-                None => "".to_string(),
-                Some(doc) => match doc {
-                    Documentation::String(st) => st.clone(),
-                    Documentation::MarkupContent(mup) => match mup.kind {
-                        MarkupKind::PlainText => mup.value.clone(),
-                        // NOTE just in case server ignored our documentationFormat capability
-                        // we want to unescape markdown to make text a bit more readable
-                        MarkupKind::Markdown => unescape_markdown_re
-                            .replace_all(&mup.value, r"$c")
-                            .to_string(),
-                    },
-                },
-            };
-            if let Some(d) = x.detail {
-                doc = format!("{}\n\n{}", d, doc);
-            }
+            let doc = completion_item_doc(x);
             let mut entry = x.label.clone();
             if let Some(k) = x.kind {
                 entry += &std::iter::repeat(" ")
@@ -74,7 +63,7 @@ pub fn editor_completion(
             }
             format!(
                 "'{}|{}|{}'",
-                escape(&x.insert_text.unwrap_or(x.label)),
+                escape(&item_text(x, &meta.buffile).text),
                 escape(&doc),
                 escape(&entry),
             )
@@ -82,12 +71,240 @@ pub fn editor_completion(
         .collect::<Vec<String>>()
         .join(" ");
     let p = params.position;
+    // NOTE offset comes from the editor and could exceed the column in odd cases
+    let start = Position::new(p.line, p.character.saturating_sub(params.completion.offset));
+    ctx.completion = CompletionCache {
+        start,
+        items,
+        selected: None,
+    };
     let command = format!(
         "set window lsp_completions {}.{}@{} {}\n",
-        p.line + 1,
-        p.character + 1 - params.completion.offset,
+        start.line + 1,
+        start.character + 1,
         meta.version,
-        items
+        formatted_items
+    );
+    ctx.exec(meta.clone(), command);
+}
+
+/// Resolve the completion item selected in the menu to show its documentation.
+pub fn completion_item_resolve(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    let params = CompletionItemParams::deserialize(params);
+    if params.is_err() {
+        error!("Params should follow CompletionItemParams structure");
+        return;
+    }
+    let index = inserted_item(&params.unwrap(), meta, ctx);
+    if index.is_none() || index == ctx.completion.selected {
+        return;
+    }
+    ctx.completion.selected = index;
+    let index = index.unwrap();
+    if !resolve_provider(ctx) || ctx.completion.items[index].documentation.is_some() {
+        return;
+    }
+    request_completion_item_resolve(meta, index, false, ctx);
+}
+
+fn resolve_provider(ctx: &Context) -> bool {
+    ctx.capabilities
+        .as_ref()
+        .and_then(|x| x.completion_provider.as_ref())
+        .and_then(|x| x.resolve_provider)
+        .unwrap_or(false)
+}
+
+fn request_completion_item_resolve(
+    meta: &EditorMeta,
+    index: usize,
+    accept: bool,
+    ctx: &mut Context,
+) {
+    let item = serde_json::to_value(&ctx.completion.items[index]).unwrap();
+    let id = ctx.next_request_id();
+    ctx.response_waitlist.insert(
+        id.clone(),
+        (
+            meta.clone(),
+            request::ResolveCompletionItem::METHOD.into(),
+            toml::Value::try_from(CompletionItemIndexParams { index, accept }).unwrap(),
+        ),
+    );
+    ctx.call(id, request::ResolveCompletionItem::METHOD.into(), item);
+}
+
+pub fn editor_completion_item_resolve(
+    meta: &EditorMeta,
+    params: &CompletionItemIndexParams,
+    result: CompletionItem,
+    ctx: &mut Context,
+) {
+    let index = params.index;
+    // NOTE completion items could be replaced while we were waiting for the response
+    let is_same_item = ctx
+        .completion
+        .items
+        .get(index)
+        .map(|x| x.label == result.label)
+        .unwrap_or(false);
+    if !is_same_item {
+        return;
+    }
+    let doc = completion_item_doc(&result);
+    ctx.completion.items[index] = result;
+    if params.accept {
+        apply_completion_item_edits(meta, index, ctx);
+        return;
+    }
+    if ctx.completion.selected != Some(index) || doc.is_empty() {
+        return;
+    }
+    let command = format!("info '{}'", doc.replace("'", "''"));
+    ctx.exec(meta.clone(), command);
+}
+
+/// Finish insertion of the accepted completion item: expose placeholders of its snippet to the
+/// editor, apply its additional edits (e.g. auto-imports) and run its command.
+pub fn completion_item_accept(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    let params = CompletionItemParams::deserialize(params);
+    if params.is_err() {
+        error!("Params should follow CompletionItemParams structure");
+        return;
+    }
+    let index = inserted_item(&params.unwrap(), meta, ctx);
+    if index.is_none() {
+        return;
+    }
+    let index = index.unwrap();
+    show_snippet_placeholders(meta, index, ctx);
+    // NOTE servers often compute additional edits lazily, so they could be missing until resolve
+    if ctx.completion.items[index].additional_text_edits.is_none() && resolve_provider(ctx) {
+        request_completion_item_resolve(meta, index, true, ctx);
+    } else {
+        apply_completion_item_edits(meta, index, ctx);
+    }
+}
+
+fn apply_completion_item_edits(meta: &EditorMeta, index: usize, ctx: &mut Context) {
+    // NOTE edits could arrive with resolve response when user has already changed the buffer
+    if ctx.is_outdated(meta) {
+        ctx.reject_outdated_edits(meta, "completionItem/accept");
+        return;
+    }
+    let item = &ctx.completion.items[index];
+    let edits = item.additional_text_edits.clone().unwrap_or_default();
+    let command = item.command.clone();
+    let uri = Url::from_file_path(&meta.buffile).unwrap();
+    if !apply_text_edits(meta, &uri, &edits, ctx) {
+        return;
+    }
+    if let Some(command) = command {
+        workspace::execute_command(meta, command, ctx);
+    }
+}
+
+fn show_snippet_placeholders(meta: &EditorMeta, index: usize, ctx: &mut Context) {
+    let snippet = item_text(&ctx.completion.items[index], &meta.buffile);
+    if snippet.placeholders.is_empty() {
+        return;
+    }
+    let start = ctx.completion.start;
+    let ranges = snippet
+        .placeholders
+        .iter()
+        .map(|&(start_offset, end_offset)| {
+            // NOTE Kakoune ranges are inclusive, so range ends at the last character of the
+            // placeholder; empty placeholder is represented by the character following it
+            let last_offset = snippet.text[start_offset..end_offset]
+                .char_indices()
+                .last()
+                .map(|(i, _)| start_offset + i)
+                .unwrap_or(start_offset);
+            let (start_line, start_column) = offset_to_kakoune(&snippet.text, start_offset, start);
+            let (end_line, end_column) = offset_to_kakoune(&snippet.text, last_offset, start);
+            format!(
+                "{}.{},{}.{}|SnippetPlaceholder",
+                start_line, start_column, end_line, end_column
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    let command = format!(
+        "eval -buffer %§{}§ %§set buffer lsp_snippets_placeholders {} {}§",
+        meta.buffile, meta.version, ranges
     );
     ctx.exec(meta.clone(), command);
 }
+
+/// Find the item which text was inserted by the completion menu before the cursor.
+fn inserted_item(params: &CompletionItemParams, meta: &EditorMeta, ctx: &Context) -> Option<usize> {
+    let start = ctx.completion.start;
+    let is_inserted = |item: &CompletionItem| {
+        let text = item_text(item, &meta.buffile).text;
+        let lines = text.split('\n').collect::<Vec<_>>();
+        let last_line = lines[lines.len() - 1];
+        if lines.len() == 1 {
+            params.position.line == start.line
+                && params.line_prefix.get(start.character as usize..) == Some(last_line)
+        } else {
+            params.position.line == start.line + lines.len() as u64 - 1
+                && params.line_prefix == last_line
+        }
+    };
+    // NOTE several items could insert the same text, e.g. the same name to be imported from
+    // different modules, so the item which was selected in the menu takes precedence
+    if let Some(selected) = ctx.completion.selected {
+        if is_inserted(&ctx.completion.items[selected]) {
+            return Some(selected);
+        }
+    }
+    ctx.completion.items.iter().position(is_inserted)
+}
+
+/// Return text which completion item inserts, snippets are expanded.
+fn item_text(item: &CompletionItem, buffile: &str) -> Snippet {
+    let text = item.insert_text.as_ref().unwrap_or(&item.label);
+    match item.insert_text_format {
+        Some(InsertTextFormat::Snippet) => snippet::expand(text, buffile),
+        _ => Snippet {
+            text: text.clone(),
+            placeholders: vec![],
+        },
+    }
+}
+
+/// Convert byte offset in the text inserted at `start` into 1-based Kakoune coordinates.
+fn offset_to_kakoune(text: &str, offset: usize, start: Position) -> (u64, u64) {
+    let before = &text[..offset];
+    match before.rfind('\n') {
+        Some(i) => (
+            start.line + before.matches('\n').count() as u64 + 1,
+            (offset - i) as u64,
+        ),
+        None => (start.line + 1, start.character + offset as u64 + 1),
+    }
+}
+
+/// Join item detail and documentation into a text suitable for the completion docstring.
+fn completion_item_doc(item: &CompletionItem) -> String {
+    let unescape_markdown_re = Regex::new(r"\\(?P<c>.)").unwrap();
+    let mut doc: String = match &item.documentation {
+        None => "".to_string(),
+        Some(doc) => match doc {
+            Documentation::String(st) => st.clone(),
+            Documentation::MarkupContent(mup) => match mup.kind {
+                MarkupKind::PlainText => mup.value.clone(),
+                // NOTE just in case server ignored our documentationFormat capability
+                // we want to unescape markdown to make text a bit more readable
+                MarkupKind::Markdown => unescape_markdown_re
+                    .replace_all(&mup.value, r"$c")
+                    .to_string(),
+            },
+        },
+    };
+    if let Some(ref d) = item.detail {
+        doc = format!("{}\n\n{}", d, doc);
+    }
+    doc
+}
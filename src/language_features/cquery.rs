@@ -1,5 +1,6 @@
 use context::*;
 use languageserver_types::{NumberOrString, Range};
+use position::PositionConverter;
 use serde;
 use types::*;
 use url::Url;
@@ -163,12 +164,15 @@ pub fn publish_semantic_highlighting(params: PublishSemanticHighlightingParams,
         return;
     }
     let version = *version.unwrap();
+    let converter = PositionConverter::new(buffile, ctx);
     let ranges = params
         .symbols
         .iter()
         .flat_map(|x| {
             let face = x.get_face();
+            let converter = &converter;
             x.ranges.iter().filter_map(move |r| {
+                let r = converter.range_to_kakoune(*r);
                 if face.is_empty() {
                     warn!("No face found for {:?}", x);
                     Option::None
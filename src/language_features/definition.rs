@@ -1,17 +1,56 @@
 use context::*;
+use language_features::references::format_locations;
 use languageserver_types::request::Request;
 use languageserver_types::*;
+use position::PositionConverter;
 use serde::Deserialize;
 use types::*;
 use url::Url;
 
 pub fn text_document_definition(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    text_document_goto(request::GotoDefinition::METHOD, params, meta, ctx);
+}
+
+pub fn text_document_implementation(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    if !ctx.extended_capabilities.implementation_provider.is_supported() {
+        unsupported_method(request::GotoImplementation::METHOD, meta, ctx);
+        return;
+    }
+    text_document_goto(request::GotoImplementation::METHOD, params, meta, ctx);
+}
+
+pub fn text_document_type_definition(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    if !ctx.extended_capabilities.type_definition_provider.is_supported() {
+        unsupported_method(request::GotoTypeDefinition::METHOD, meta, ctx);
+        return;
+    }
+    text_document_goto(request::GotoTypeDefinition::METHOD, params, meta, ctx);
+}
+
+pub fn text_document_declaration(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    if !ctx.extended_capabilities.declaration_provider.is_supported() {
+        unsupported_method(GOTO_DECLARATION_METHOD, meta, ctx);
+        return;
+    }
+    text_document_goto(GOTO_DECLARATION_METHOD, params, meta, ctx);
+}
+
+fn unsupported_method(method: &str, meta: &EditorMeta, ctx: &mut Context) {
+    let command = format!(
+        "lsp-show-error %§{} language server doesn't support method {}§",
+        ctx.language_id, method
+    );
+    ctx.exec(meta.clone(), command);
+}
+
+fn text_document_goto(method: &str, params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
     let req_params = PositionParams::deserialize(params.clone());
     if req_params.is_err() {
         error!("Params should follow PositionParams structure");
+        return;
     }
     let req_params = req_params.unwrap();
-    let position = req_params.position;
+    let position = PositionConverter::new(&meta.buffile, ctx).to_lsp(req_params.position);
     let req_params = TextDocumentPositionParams {
         text_document: TextDocumentIdentifier {
             uri: Url::from_file_path(&meta.buffile).unwrap(),
@@ -19,11 +58,9 @@ pub fn text_document_definition(params: EditorParams, meta: &EditorMeta, ctx: &m
         position,
     };
     let id = ctx.next_request_id();
-    ctx.response_waitlist.insert(
-        id.clone(),
-        (meta.clone(), request::GotoDefinition::METHOD.into(), params),
-    );
-    ctx.call(id, request::GotoDefinition::METHOD.into(), req_params);
+    ctx.response_waitlist
+        .insert(id.clone(), (meta.clone(), method.into(), params));
+    ctx.call(id, method.into(), req_params);
 }
 
 pub fn editor_definition(
@@ -32,18 +69,22 @@ pub fn editor_definition(
     result: GotoDefinitionResponse,
     ctx: &mut Context,
 ) {
-    if let Some(location) = match result {
-        GotoDefinitionResponse::Scalar(location) => Some(location),
-        GotoDefinitionResponse::Array(mut locations) => if locations.is_empty() {
-            None
-        } else {
-            Some(locations.remove(0))
-        },
-        GotoDefinitionResponse::None => None,
-    } {
+    let mut locations = match result {
+        GotoDefinitionResponse::Scalar(location) => vec![location],
+        GotoDefinitionResponse::Array(locations) => locations,
+        GotoDefinitionResponse::None => vec![],
+    };
+    if locations.len() > 1 {
+        let content = format_locations(locations, ctx);
+        let command = format!(
+            "lsp-show-goto-choices %§{}§ %§{}§",
+            ctx.root_path, content,
+        );
+        ctx.exec(meta.clone(), command);
+    } else if let Some(location) = locations.pop() {
         let path = location.uri.to_file_path().unwrap();
         let filename = path.to_str().unwrap();
-        let p = location.range.start;
+        let p = PositionConverter::new(filename, ctx).to_kakoune(location.range.start);
         let command = format!("edit %§{}§ {} {}", filename, p.line + 1, p.character + 1);
         ctx.exec(meta.clone(), command);
     };
@@ -0,0 +1,205 @@
+use context::*;
+use languageserver_types::*;
+use position::PositionConverter;
+use serde::Deserialize;
+use text_edit::apply_text_edits;
+use types::*;
+use url::Url;
+use util::lsp_range_to_kakoune;
+
+pub fn text_document_document_color(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    // NOTE colors are requested automatically, so don't bother user if server doesn't support them
+    if !ctx.extended_capabilities.color_provider.is_supported() {
+        return;
+    }
+    request_document_colors(params, meta, ctx);
+}
+
+fn request_document_colors(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    let req_params = DocumentColorParams {
+        text_document: TextDocumentIdentifier {
+            uri: Url::from_file_path(&meta.buffile).unwrap(),
+        },
+    };
+    let id = ctx.next_request_id();
+    ctx.response_waitlist.insert(
+        id.clone(),
+        (meta.clone(), DOCUMENT_COLOR_METHOD.into(), params),
+    );
+    ctx.call(id, DOCUMENT_COLOR_METHOD.into(), req_params);
+}
+
+pub fn editor_document_color(
+    meta: &EditorMeta,
+    params: EditorParams,
+    result: Option<Vec<ColorInformation>>,
+    ctx: &mut Context,
+) {
+    let colors = result.unwrap_or_default();
+    let mut faces = colors
+        .iter()
+        .map(|x| color_face(&x.color))
+        .collect::<Vec<_>>();
+    faces.sort_unstable();
+    faces.dedup();
+    // NOTE faces are defined on the fly, one per each distinct color
+    let set_faces = faces
+        .iter()
+        .map(|(name, spec)| format!("set-face global {} {}", name, spec))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let converter = PositionConverter::new(&meta.buffile, ctx);
+    let ranges = colors
+        .iter()
+        .map(|x| {
+            format!(
+                "{}|{}",
+                lsp_range_to_kakoune(&converter.range_to_kakoune(x.range)),
+                color_face(&x.color).0
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    ctx.document_colors
+        .insert(meta.buffile.clone(), (meta.version, colors));
+    let command = format!(
+        "eval -buffer %§{}§ %§{}\nset buffer lsp_document_colors {} {}§",
+        meta.buffile, set_faces, meta.version, ranges
+    );
+    ctx.exec(meta.clone(), command);
+    // NOTE colors are requested with position only when user wants to change presentation
+    if TextDocumentColorPresentationParams::deserialize(params.clone()).is_ok() {
+        request_color_presentation(meta, params, ctx);
+    }
+}
+
+/// Return face name and face spec which shows color as a background.
+fn color_face(color: &Color) -> (String, String) {
+    let to_byte = |x: f64| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let rgb = format!(
+        "{:02x}{:02x}{:02x}",
+        to_byte(color.red),
+        to_byte(color.green),
+        to_byte(color.blue)
+    );
+    // NOTE pick foreground which is readable on the color
+    let luminance = 0.299 * color.red + 0.587 * color.green + 0.114 * color.blue;
+    let foreground = if luminance > 0.5 { "black" } else { "white" };
+    (
+        format!("LspColor_{}", rgb),
+        format!("{},rgb:{}", foreground, rgb),
+    )
+}
+
+pub fn text_document_color_presentation(
+    params: EditorParams,
+    meta: &EditorMeta,
+    ctx: &mut Context,
+) {
+    if !ctx.extended_capabilities.color_provider.is_supported() {
+        let command = format!(
+            "lsp-show-error %§{} language server doesn't support method {}§",
+            ctx.language_id, COLOR_PRESENTATION_METHOD
+        );
+        ctx.exec(meta.clone(), command);
+        return;
+    }
+    if TextDocumentColorPresentationParams::deserialize(params.clone()).is_err() {
+        error!("Params should follow TextDocumentColorPresentationParams structure");
+        return;
+    }
+    let is_cached = ctx
+        .document_colors
+        .get(&meta.buffile)
+        .map(|(version, _)| *version == meta.version)
+        .unwrap_or(false);
+    if is_cached {
+        request_color_presentation(meta, params, ctx);
+    } else {
+        request_document_colors(params, meta, ctx);
+    }
+}
+
+fn color_at(meta: &EditorMeta, position: Position, ctx: &Context) -> Option<ColorInformation> {
+    let position = PositionConverter::new(&meta.buffile, ctx).to_lsp(position);
+    ctx.document_colors
+        .get(&meta.buffile)
+        .and_then(|(_, colors)| {
+            colors
+                .iter()
+                .find(|x| x.range.start <= position && position < x.range.end)
+                .cloned()
+        })
+}
+
+fn request_color_presentation(meta: &EditorMeta, params: EditorParams, ctx: &mut Context) {
+    let position = TextDocumentColorPresentationParams::deserialize(params.clone())
+        .unwrap()
+        .position;
+    let color = color_at(meta, position, ctx);
+    if color.is_none() {
+        ctx.exec(meta.clone(), "info 'No color under cursor'".to_string());
+        return;
+    }
+    let ColorInformation { range, color } = color.unwrap();
+    let req_params = ColorPresentationParams {
+        text_document: TextDocumentIdentifier {
+            uri: Url::from_file_path(&meta.buffile).unwrap(),
+        },
+        color,
+        range,
+    };
+    let id = ctx.next_request_id();
+    ctx.response_waitlist.insert(
+        id.clone(),
+        (meta.clone(), COLOR_PRESENTATION_METHOD.into(), params),
+    );
+    ctx.call(id, COLOR_PRESENTATION_METHOD.into(), req_params);
+}
+
+pub fn editor_color_presentation(
+    meta: &EditorMeta,
+    params: &TextDocumentColorPresentationParams,
+    result: Vec<ColorPresentation>,
+    ctx: &mut Context,
+) {
+    let color = color_at(meta, params.position, ctx);
+    if color.is_none() || result.is_empty() {
+        return;
+    }
+    let range = color.unwrap().range;
+    let start = PositionConverter::new(&meta.buffile, ctx).to_kakoune(range.start);
+    let edits = result
+        .into_iter()
+        .map(|x| {
+            let ColorPresentation {
+                label,
+                text_edit,
+                additional_text_edits,
+            } = x;
+            let mut edits = vec![text_edit.unwrap_or(TextEdit {
+                range,
+                new_text: label,
+            })];
+            edits.extend(additional_text_edits.unwrap_or_default());
+            edits
+        })
+        .collect::<Vec<_>>();
+    // NOTE the current presentation is the one which text is already at the color range,
+    // we replace it with the next one
+    let current = edits.iter().position(|x| {
+        params
+            .line_text
+            .get(start.character as usize..)
+            .map(|text| text.starts_with(&x[0].new_text))
+            .unwrap_or(false)
+    });
+    let next = current.map(|i| (i + 1) % edits.len()).unwrap_or(0);
+    let uri = Url::from_file_path(&meta.buffile).unwrap();
+    if apply_text_edits(meta, &uri, &edits[next], ctx) {
+        ctx.exec(
+            meta.clone(),
+            "lsp-did-change; lsp-document-color-request".to_string(),
+        );
+    }
+}
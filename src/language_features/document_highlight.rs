@@ -0,0 +1,71 @@
+use context::*;
+use languageserver_types::request::Request;
+use languageserver_types::*;
+use position::PositionConverter;
+use serde::Deserialize;
+use types::*;
+use url::Url;
+use util::lsp_range_to_kakoune;
+
+pub fn text_document_document_highlight(
+    params: EditorParams,
+    meta: &EditorMeta,
+    ctx: &mut Context,
+) {
+    let req_params = PositionParams::deserialize(params.clone());
+    if req_params.is_err() {
+        error!("Params should follow PositionParams structure");
+        return;
+    }
+    let req_params = req_params.unwrap();
+    let position = PositionConverter::new(&meta.buffile, ctx).to_lsp(req_params.position);
+    let req_params = TextDocumentPositionParams {
+        text_document: TextDocumentIdentifier {
+            uri: Url::from_file_path(&meta.buffile).unwrap(),
+        },
+        position,
+    };
+    let id = ctx.next_request_id();
+    ctx.response_waitlist.insert(
+        id.clone(),
+        (
+            meta.clone(),
+            request::DocumentHighlightRequest::METHOD.into(),
+            params,
+        ),
+    );
+    ctx.call(
+        id,
+        request::DocumentHighlightRequest::METHOD.into(),
+        req_params,
+    );
+}
+
+pub fn editor_document_highlight(
+    meta: &EditorMeta,
+    result: Option<Vec<DocumentHighlight>>,
+    ctx: &mut Context,
+) {
+    let converter = PositionConverter::new(&meta.buffile, ctx);
+    let ranges = result
+        .unwrap_or_default()
+        .iter()
+        .map(|x| {
+            format!(
+                "{}|{}",
+                lsp_range_to_kakoune(&converter.range_to_kakoune(x.range)),
+                match x.kind {
+                    Some(DocumentHighlightKind::Read) => "DocumentHighlightRead",
+                    Some(DocumentHighlightKind::Write) => "DocumentHighlightWrite",
+                    _ => "DocumentHighlightText",
+                }
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(" ");
+    let command = format!(
+        "eval -buffer %§{}§ %§set buffer lsp_document_highlight {} {}§",
+        meta.buffile, meta.version, ranges
+    );
+    ctx.exec(meta.clone(), command);
+}
@@ -0,0 +1,194 @@
+use context::*;
+use languageserver_types::request::Request;
+use languageserver_types::*;
+use position::PositionConverter;
+use serde::Deserialize;
+use toml;
+use types::DocumentLink;
+use types::*;
+use url::Url;
+use util::lsp_range_to_kakoune;
+
+pub fn text_document_document_link(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    // NOTE links are requested automatically, so don't bother user if server doesn't support them
+    if ctx.extended_capabilities.document_link_provider.is_none() {
+        return;
+    }
+    request_document_links(params, meta, ctx);
+}
+
+fn request_document_links(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    let req_params = DocumentLinkParams {
+        text_document: TextDocumentIdentifier {
+            uri: Url::from_file_path(&meta.buffile).unwrap(),
+        },
+    };
+    let id = ctx.next_request_id();
+    ctx.response_waitlist.insert(
+        id.clone(),
+        (
+            meta.clone(),
+            request::DocumentLinkRequest::METHOD.into(),
+            params,
+        ),
+    );
+    ctx.call(id, request::DocumentLinkRequest::METHOD.into(), req_params);
+}
+
+pub fn editor_document_link(
+    meta: &EditorMeta,
+    params: EditorParams,
+    result: Option<Vec<DocumentLink>>,
+    ctx: &mut Context,
+) {
+    let links = result.unwrap_or_default();
+    let converter = PositionConverter::new(&meta.buffile, ctx);
+    let ranges = links
+        .iter()
+        .map(|x| {
+            format!(
+                "{}|DocumentLink",
+                lsp_range_to_kakoune(&converter.range_to_kakoune(x.range))
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(" ");
+    ctx.document_links
+        .insert(meta.buffile.clone(), (meta.version, links));
+    let command = format!(
+        "eval -buffer %§{}§ %§set buffer lsp_document_links {} {}§",
+        meta.buffile, meta.version, ranges
+    );
+    ctx.exec(meta.clone(), command);
+    // NOTE links are requested with position only when user wants to open one of them
+    if let Ok(params) = PositionParams::deserialize(params) {
+        open_link_at(meta, params.position, ctx);
+    }
+}
+
+pub fn document_link_open(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    if ctx.extended_capabilities.document_link_provider.is_none() {
+        let command = format!(
+            "lsp-show-error %§{} language server doesn't support method {}§",
+            ctx.language_id,
+            request::DocumentLinkRequest::METHOD
+        );
+        ctx.exec(meta.clone(), command);
+        return;
+    }
+    let position = PositionParams::deserialize(params.clone());
+    if position.is_err() {
+        error!("Params should follow PositionParams structure");
+        return;
+    }
+    let position = position.unwrap().position;
+    let is_cached = ctx
+        .document_links
+        .get(&meta.buffile)
+        .map(|(version, _)| *version == meta.version)
+        .unwrap_or(false);
+    if is_cached {
+        open_link_at(meta, position, ctx);
+    } else {
+        request_document_links(params, meta, ctx);
+    }
+}
+
+fn open_link_at(meta: &EditorMeta, position: Position, ctx: &mut Context) {
+    let position = PositionConverter::new(&meta.buffile, ctx).to_lsp(position);
+    let link = ctx
+        .document_links
+        .get(&meta.buffile)
+        .and_then(|(_, links)| {
+            links
+                .iter()
+                .find(|x| range_contains(&x.range, position))
+                .cloned()
+        });
+    if link.is_none() {
+        ctx.exec(meta.clone(), "info 'No link under cursor'".to_string());
+        return;
+    }
+    let link = link.unwrap();
+    if link.target.is_some() {
+        open_link(meta, &link, ctx);
+        return;
+    }
+    let resolve_provider = ctx
+        .extended_capabilities
+        .document_link_provider
+        .as_ref()
+        .and_then(|x| x.resolve_provider)
+        .unwrap_or(false);
+    if !resolve_provider {
+        ctx.exec(meta.clone(), "info 'Link has no target'".to_string());
+        return;
+    }
+    let id = ctx.next_request_id();
+    ctx.response_waitlist.insert(
+        id.clone(),
+        (
+            meta.clone(),
+            request::DocumentLinkResolve::METHOD.into(),
+            toml::Value::Table(toml::value::Table::default()),
+        ),
+    );
+    ctx.call(id, request::DocumentLinkResolve::METHOD.into(), link);
+}
+
+pub fn editor_document_link_resolve(meta: &EditorMeta, result: DocumentLink, ctx: &mut Context) {
+    if let Some((_, links)) = ctx.document_links.get_mut(&meta.buffile) {
+        if let Some(link) = links.iter_mut().find(|x| x.range == result.range) {
+            *link = result.clone();
+        }
+    }
+    if result.target.is_none() {
+        ctx.exec(meta.clone(), "info 'Link has no target'".to_string());
+        return;
+    }
+    open_link(meta, &result, ctx);
+}
+
+fn range_contains(range: &Range, position: Position) -> bool {
+    let start = (range.start.line, range.start.character);
+    let end = (range.end.line, range.end.character);
+    let position = (position.line, position.character);
+    start <= position && position < end
+}
+
+fn open_link(meta: &EditorMeta, link: &DocumentLink, ctx: &mut Context) {
+    let target = link.target.as_ref().unwrap();
+    let url = Url::parse(target);
+    if url.is_err() {
+        error!("Failed to parse link target: {}", target);
+        return;
+    }
+    let url = url.unwrap();
+    if url.scheme() != "file" {
+        ctx.exec(meta.clone(), format!("lsp-open-url %§{}§", target));
+        return;
+    }
+    let path = url.to_file_path();
+    if path.is_err() {
+        error!("Failed to convert link target to path: {}", target);
+        return;
+    }
+    let path = path.unwrap();
+    let mut command = format!("edit %§{}§", path.to_str().unwrap());
+    // NOTE there is no standard way to point into the file, but servers tend to follow
+    // VSCode convention of `#L<line>` or `#L<line>,<column>` fragment
+    if let Some(fragment) = url.fragment() {
+        if let Some(coords) = fragment.strip_prefix('L') {
+            let coords = coords
+                .split(',')
+                .map(|x| x.parse::<u64>())
+                .collect::<Result<Vec<_>, _>>();
+            if let Ok(coords) = coords {
+                for coord in coords.iter().take(2) {
+                    command.push_str(&format!(" {}", coord));
+                }
+            }
+        }
+    }
+    ctx.exec(meta.clone(), command);
+}
@@ -1,6 +1,8 @@
 use context::*;
 use languageserver_types::request::Request;
 use languageserver_types::*;
+use position::PositionConverter;
+use std::path::PathBuf;
 use types::*;
 use url::Url;
 
@@ -30,7 +32,19 @@ pub fn editor_document_symbol(
     if result.is_empty() {
         return;
     }
-    let content = result
+    let content = format_symbol_information(result, ctx);
+    let command = format!(
+        "lsp-show-document-symbol %§{}§ %§{}§",
+        ctx.root_path, content,
+    );
+    ctx.exec(meta.clone(), command);
+}
+
+/// Render symbols as grep-like lines with paths relative to the project root.
+pub fn format_symbol_information(result: Vec<SymbolInformation>, ctx: &Context) -> String {
+    // NOTE symbols are mostly grouped by file, so converter is reused while file is the same
+    let mut converter: Option<(PathBuf, PositionConverter)> = None;
+    result
         .into_iter()
         .map(|symbol| {
             let SymbolInformation {
@@ -39,15 +53,20 @@ pub fn editor_document_symbol(
                 kind,
                 ..
             } = symbol;
-            let filename = location.uri.to_file_path().unwrap();
-            let filename = filename
+            let path = location.uri.to_file_path().unwrap();
+            if converter.as_ref().map(|(x, _)| *x != path).unwrap_or(true) {
+                let file_converter = PositionConverter::new(path.to_str().unwrap(), ctx);
+                converter = Some((path.clone(), file_converter));
+            }
+            let (_, file_converter) = converter.as_ref().unwrap();
+            let position = file_converter.to_kakoune(location.range.start);
+            let filename = path
                 .strip_prefix(&ctx.root_path)
                 .ok()
                 .and_then(|p| Some(p.to_str().unwrap()))
-                .or_else(|| filename.to_str())
+                .or_else(|| path.to_str())
                 .unwrap();
 
-            let position = location.range.start;
             let description = format!("{:?} {}", kind, name);
             format!(
                 "{}:{}:{}:{}",
@@ -58,10 +77,5 @@ pub fn editor_document_symbol(
             )
         })
         .collect::<Vec<_>>()
-        .join("\n");
-    let command = format!(
-        "lsp-show-document-symbol %§{}§ %§{}§",
-        ctx.root_path, content,
-    );
-    ctx.exec(meta.clone(), command);
+        .join("\n")
 }
@@ -0,0 +1,165 @@
+use context::*;
+use languageserver_types::*;
+use position::PositionConverter;
+use serde::Deserialize;
+use std::cmp::Reverse;
+use std::path::Path;
+use types::*;
+use url::Url;
+use util::lsp_range_to_kakoune;
+
+pub fn text_document_folding_range(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    if !ctx
+        .extended_capabilities
+        .folding_range_provider
+        .is_supported()
+    {
+        let command = format!(
+            "lsp-show-error %§{} language server doesn't support method {}§",
+            ctx.language_id, FOLDING_RANGE_METHOD
+        );
+        ctx.exec(meta.clone(), command);
+        return;
+    }
+    if TextDocumentFoldingRangeParams::deserialize(params.clone()).is_err() {
+        error!("Params should follow TextDocumentFoldingRangeParams structure");
+        return;
+    }
+    let req_params = FoldingRangeParams {
+        text_document: TextDocumentIdentifier {
+            uri: Url::from_file_path(&meta.buffile).unwrap(),
+        },
+    };
+    let id = ctx.next_request_id();
+    ctx.response_waitlist.insert(
+        id.clone(),
+        (meta.clone(), FOLDING_RANGE_METHOD.into(), params),
+    );
+    ctx.call(id, FOLDING_RANGE_METHOD.into(), req_params);
+}
+
+pub fn editor_folding_range(
+    meta: &EditorMeta,
+    params: &TextDocumentFoldingRangeParams,
+    result: Option<Vec<FoldingRange>>,
+    ctx: &mut Context,
+) {
+    let mut ranges = result.unwrap_or_default();
+    // NOTE sort outer ranges before inner ones, it's expected by show_folding_ranges
+    ranges.sort_by_key(|x| (x.start_line, Reverse(x.end_line)));
+    if let Some(position) = params.position {
+        select_innermost_folding_range(meta, position, &ranges, ctx);
+    } else if let Some(ref kind) = params.kind {
+        select_folding_ranges_of_kind(meta, kind, &ranges, ctx);
+    } else {
+        show_folding_ranges(meta, &ranges, ctx);
+    }
+}
+
+/// Convert folding range into LSP range. Folding range without characters spans whole lines.
+fn to_range(folding_range: &FoldingRange) -> Range {
+    let start = Position::new(
+        folding_range.start_line,
+        folding_range.start_character.unwrap_or(0),
+    );
+    let end = match folding_range.end_character {
+        Some(character) => Position::new(folding_range.end_line, character),
+        None => Position::new(folding_range.end_line + 1, 0),
+    };
+    Range::new(start, end)
+}
+
+fn select_innermost_folding_range(
+    meta: &EditorMeta,
+    position: Position,
+    ranges: &[FoldingRange],
+    ctx: &mut Context,
+) {
+    let converter = PositionConverter::new(&meta.buffile, ctx);
+    // NOTE ranges are sorted from outer to inner ones, so the last one around position is innermost
+    let innermost = ranges
+        .iter()
+        .map(|x| converter.range_to_kakoune(to_range(x)))
+        .rev()
+        .find(|range| {
+            (range.start.line, range.start.character) <= (position.line, position.character)
+                && (position.line, position.character) < (range.end.line, range.end.character)
+        });
+    match innermost {
+        Some(range) => {
+            let command = format!("select {}", lsp_range_to_kakoune(&range));
+            ctx.exec(meta.clone(), command);
+        }
+        None => {
+            ctx.exec(
+                meta.clone(),
+                "info 'No folding range around cursor'".to_string(),
+            );
+        }
+    }
+}
+
+fn select_folding_ranges_of_kind(
+    meta: &EditorMeta,
+    kind: &str,
+    ranges: &[FoldingRange],
+    ctx: &mut Context,
+) {
+    let converter = PositionConverter::new(&meta.buffile, ctx);
+    let selections = ranges
+        .iter()
+        .filter(|x| x.kind.as_ref().map(|x| x == kind).unwrap_or(false))
+        .map(|x| lsp_range_to_kakoune(&converter.range_to_kakoune(to_range(x))))
+        .collect::<Vec<_>>();
+    if selections.is_empty() {
+        let command = format!("info %§No folding ranges of kind {}§", kind);
+        ctx.exec(meta.clone(), command);
+        return;
+    }
+    let command = format!("select {}", selections.join(" "));
+    ctx.exec(meta.clone(), command);
+}
+
+fn show_folding_ranges(meta: &EditorMeta, ranges: &[FoldingRange], ctx: &mut Context) {
+    if ranges.is_empty() {
+        ctx.exec(meta.clone(), "info 'No folding ranges'".to_string());
+        return;
+    }
+    let filename = Path::new(&meta.buffile)
+        .strip_prefix(&ctx.root_path)
+        .ok()
+        .and_then(|p| p.to_str())
+        .unwrap_or(&meta.buffile);
+    // NOTE ends of enclosing ranges, nesting depth is the size of the stack
+    let mut enclosing: Vec<u64> = vec![];
+    let content = ranges
+        .iter()
+        .map(|x| {
+            while enclosing
+                .last()
+                .map(|end| *end < x.start_line)
+                .unwrap_or(false)
+            {
+                enclosing.pop();
+            }
+            let line = format!(
+                "{}:{}:{}:{}{} ({}-{})",
+                filename,
+                x.start_line + 1,
+                x.start_character.unwrap_or(0) + 1,
+                "  ".repeat(enclosing.len()),
+                x.kind.as_deref().unwrap_or("block"),
+                x.start_line + 1,
+                x.end_line + 1
+            );
+            enclosing.push(x.end_line);
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let command = format!(
+        "lsp-show-folding-ranges %§{}§ %§{}§",
+        ctx.root_path, content,
+    );
+    ctx.exec(meta.clone(), command);
+}
@@ -1,9 +1,16 @@
 use context::*;
 use languageserver_types::request::Request;
 use languageserver_types::*;
+use position::PositionConverter;
 use serde::Deserialize;
+use serde_json::{self, Value};
+use std::collections::HashMap;
+use text_edit::apply_text_edits_to_buffer;
+use text_sync;
+use toml;
 use types::*;
 use url::Url;
+use util::parse_kakoune_range;
 
 pub fn text_document_formatting(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
     let options = FormattingOptions::deserialize(params.clone());
@@ -25,10 +32,6 @@ pub fn text_document_formatting(params: EditorParams, meta: &EditorMeta, ctx: &m
     ctx.call(id, request::Formatting::METHOD.into(), req_params);
 }
 
-fn escape(s: &str) -> String {
-    s.replace("'", "''")
-}
-
 pub fn editor_formatting(
     meta: &EditorMeta,
     _params: &FormattingOptions,
@@ -36,74 +39,210 @@ pub fn editor_formatting(
     ctx: &mut Context,
 ) {
     if let TextEditResponse::Array(text_edits) = result {
-        let edits = text_edits
-            .iter()
-            .map(|text_edit| {
-                let TextEdit { range, new_text } = text_edit;
-                // LSP ranges are 0-based, but Kakoune's 1-based.
-                // LSP ranges are exclusive, but Kakoune's are inclusive.
-                // Also from LSP spec: If you want to specify a range that contains a line including
-                // the line ending character(s) then use an end position denoting the start of the next
-                // line.
-                let mut start_line = range.start.line;
-                let mut start_char = range.start.character;
-                let mut end_line = range.end.line;
-                let mut end_char = range.end.character;
-
-                if start_line == end_line && start_char == end_char && start_char == 0 {
-                    start_char = 1_000_000;
-                } else {
-                    start_line += 1;
-                    start_char += 1;
-                }
+        let command = apply_text_edits_to_buffer(&meta.buffile, &text_edits, ctx);
+        ctx.exec(meta.clone(), command);
+    }
+}
 
-                if end_char > 0 {
-                    end_line += 1;
-                } else {
-                    end_char = 1_000_000;
-                }
+pub fn text_document_range_formatting(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    let range_formatting_provider = ctx
+        .capabilities
+        .as_ref()
+        .and_then(|x| x.document_range_formatting_provider)
+        .unwrap_or(false);
+    if !range_formatting_provider {
+        ctx.exec(
+            meta.clone(),
+            "lsp-show-error 'Language server does not support range formatting'".to_string(),
+        );
+        return;
+    }
+    let params = TextDocumentRangeFormattingParams::deserialize(params);
+    if params.is_err() {
+        error!("Params should follow TextDocumentRangeFormattingParams structure");
+        return;
+    }
+    let params = params.unwrap();
+    let converter = PositionConverter::new(&meta.buffile, ctx);
+    let ranges = params
+        .ranges
+        .iter()
+        .map(|desc| parse_kakoune_range(desc).map(|x| converter.range_to_lsp(x)))
+        .collect::<Option<Vec<_>>>();
+    if ranges.is_none() {
+        error!("Failed to parse selections {:?}", params.ranges);
+        return;
+    }
+    let ranges = ranges.unwrap();
+    if ranges.is_empty() {
+        return;
+    }
+    // NOTE each selection is formatted by its own request, but edits are applied at once when all
+    // responses are received because they are computed against the same version of the buffer
+    let batch_id = ctx.start_batch(ranges.len(), meta.version);
+    let batch_params = toml::Value::try_from(BatchParams { batch_id }).unwrap();
+    for range in ranges {
+        let options = &params.formatting_options;
+        let req_params = DocumentRangeFormattingParams {
+            text_document: TextDocumentIdentifier {
+                uri: Url::from_file_path(&meta.buffile).unwrap(),
+            },
+            range,
+            options: FormattingOptions {
+                tab_size: options.tab_size,
+                insert_spaces: options.insert_spaces,
+                properties: HashMap::new(),
+            },
+        };
+        let id = ctx.next_request_id();
+        ctx.response_waitlist.insert(
+            id.clone(),
+            (
+                meta.clone(),
+                request::RangeFormatting::METHOD.into(),
+                batch_params.clone(),
+            ),
+        );
+        ctx.call(id, request::RangeFormatting::METHOD.into(), req_params);
+    }
+}
 
-                (
-                    format!("{}.{}", start_line, start_char),
-                    format!("{}.{}", end_line, end_char),
-                    escape(&new_text),
-                )
-            })
-            .collect::<Vec<_>>();
-        let select_edits = edits
-            .iter()
-            .map(|(start, end, _)| format!("{},{}", start, end))
-            .collect::<Vec<_>>()
-            .join(" ");
-        let apply_edits = edits
-            .iter()
-            .enumerate()
-            .map(|(i, (start, end, content))| {
-                format!(
-                    "exec 'z{}<space>'
-                    {} '{}'",
-                    if i > 0 {
-                        format!("{})", i)
-                    } else {
-                        "".to_string()
-                    },
-                    if start == end {
-                        "lsp-insert-after-selection"
-                    } else {
-                        "lsp-replace-selection"
-                    },
-                    content
-                )
-            })
-            .collect::<Vec<_>>()
-            .join("\n");
-        let command = format!(
-            "select {}
-            exec -save-regs '' Z
-            {}",
-            select_edits, apply_edits
+pub fn editor_range_formatting(
+    meta: &EditorMeta,
+    params: &BatchParams,
+    result: Value,
+    ctx: &mut Context,
+) {
+    let batch = match ctx.add_to_batch(params.batch_id, result) {
+        Some(batch) => batch,
+        None => return,
+    };
+    // NOTE formatting only some of selections or formatting a changed buffer leaves it in a state
+    // user didn't ask for, so the whole batch is discarded
+    if ctx.is_version_outdated(&meta.buffile, batch.version) {
+        ctx.reject_outdated_edits(meta, request::RangeFormatting::METHOD);
+        return;
+    }
+    if batch.failed {
+        ctx.exec(
+            meta.clone(),
+            "lsp-show-error 'Failed to format some of selections, edits are discarded'".to_string(),
         );
-        let command = format!("eval -draft -save-regs '^' '{}'", escape(&command));
+        return;
+    }
+    let mut text_edits = batch
+        .responses
+        .into_iter()
+        .filter_map(|result| match serde_json::from_value(result) {
+            Ok(TextEditResponse::Array(text_edits)) => Some(text_edits),
+            Ok(TextEditResponse::None) => None,
+            Err(e) => {
+                error!("Failed to parse range formatting response: {}", e);
+                None
+            }
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+    if text_edits.is_empty() {
+        return;
+    }
+    text_edits.sort_by_key(|text_edit| text_edit.range.start);
+    let command = apply_text_edits_to_buffer(&meta.buffile, &text_edits, ctx);
+    ctx.exec(meta.clone(), command);
+}
+
+pub fn text_document_on_type_formatting(
+    params: EditorParams,
+    meta: &EditorMeta,
+    ctx: &mut Context,
+) {
+    let req_params = TextDocumentOnTypeFormattingParams::deserialize(params.clone());
+    if req_params.is_err() {
+        error!("Params should follow TextDocumentOnTypeFormattingParams structure");
+        return;
+    }
+    // NOTE params carry draft of the buffer, so the change is synced right before the request,
+    // otherwise language server could get them in the wrong order
+    text_sync::text_document_did_change(params, meta, ctx);
+    let req_params = req_params.unwrap();
+    let req_params = DocumentOnTypeFormattingParams {
+        text_document: TextDocumentIdentifier {
+            uri: Url::from_file_path(&meta.buffile).unwrap(),
+        },
+        position: PositionConverter::new(&meta.buffile, ctx).to_lsp(req_params.position),
+        ch: req_params.ch,
+        options: req_params.formatting_options,
+    };
+    let id = ctx.next_request_id();
+    ctx.response_waitlist.insert(
+        id.clone(),
+        (
+            meta.clone(),
+            request::OnTypeFormatting::METHOD.into(),
+            toml::Value::Table(toml::value::Table::default()),
+        ),
+    );
+    ctx.call(id, request::OnTypeFormatting::METHOD.into(), req_params);
+}
+
+pub fn editor_on_type_formatting(meta: &EditorMeta, result: TextEditResponse, ctx: &mut Context) {
+    if let TextEditResponse::Array(text_edits) = result {
+        if text_edits.is_empty() {
+            return;
+        }
+        // NOTE edits are applied in draft context, so cursor stays where user is typing
+        let command = apply_text_edits_to_buffer(&meta.buffile, &text_edits, ctx);
         ctx.exec(meta.clone(), command);
     }
 }
+
+/// Set up editor hook which requests on-type formatting when user types one of trigger characters
+/// declared by language server.
+pub fn setup_on_type_formatting(meta: &EditorMeta, ctx: &mut Context) {
+    let triggers = match ctx
+        .capabilities
+        .as_ref()
+        .and_then(|x| x.document_on_type_formatting_provider.as_ref())
+    {
+        Some(options) => {
+            let mut triggers = vec![options.first_trigger_character.clone()];
+            if let Some(ref more) = options.more_trigger_character {
+                triggers.extend(more.iter().cloned());
+            }
+            triggers
+        }
+        None => return,
+    };
+    let regex = triggers
+        .iter()
+        .map(|trigger| escape_kakoune_regex(trigger))
+        .collect::<Vec<_>>()
+        .join("|");
+    let command = format!(
+        "eval -buffer %§{}§ %§lsp-on-type-formatting-setup '{}'§",
+        meta.buffile,
+        regex.replace("'", "''")
+    );
+    // NOTE buffer could be opened before any client is attached to the session
+    let meta = EditorMeta {
+        client: None,
+        ..meta.clone()
+    };
+    ctx.exec(meta, command);
+}
+
+fn escape_kakoune_regex(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '^' | '$' | '\\' | '.' | '*' | '+' | '?' | '(' | ')' | '[' | ']' | '{' | '}' | '|' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,229 @@
+use context::*;
+use languageserver_types::*;
+use position::PositionConverter;
+use serde::Deserialize;
+use toml;
+use types::*;
+use url::Url;
+
+/// Hierarchies which are rendered as an expandable tree in their own buffer.
+#[derive(Clone, Copy, Debug)]
+pub enum HierarchyKind {
+    Call,
+    Type,
+}
+
+impl HierarchyKind {
+    fn name(self) -> &'static str {
+        match self {
+            HierarchyKind::Call => "call",
+            HierarchyKind::Type => "type",
+        }
+    }
+
+    fn children_name(self) -> &'static str {
+        match self {
+            HierarchyKind::Call => "calls",
+            HierarchyKind::Type => "types",
+        }
+    }
+
+    fn prepare_method(self) -> &'static str {
+        match self {
+            HierarchyKind::Call => PREPARE_CALL_HIERARCHY_METHOD,
+            HierarchyKind::Type => PREPARE_TYPE_HIERARCHY_METHOD,
+        }
+    }
+
+    fn is_supported(self, ctx: &Context) -> bool {
+        let provider = match self {
+            HierarchyKind::Call => &ctx.extended_capabilities.call_hierarchy_provider,
+            HierarchyKind::Type => &ctx.extended_capabilities.type_hierarchy_provider,
+        };
+        provider.is_supported()
+    }
+
+    fn tree(self, ctx: &mut Context) -> &mut Hierarchy {
+        match self {
+            HierarchyKind::Call => &mut ctx.call_hierarchy,
+            HierarchyKind::Type => &mut ctx.type_hierarchy,
+        }
+    }
+}
+
+/// Request hierarchy items at the position, `params` are passed to the response handler.
+pub fn text_document_prepare_hierarchy(
+    kind: HierarchyKind,
+    position: Position,
+    params: EditorParams,
+    meta: &EditorMeta,
+    ctx: &mut Context,
+) {
+    let method = kind.prepare_method();
+    if !kind.is_supported(ctx) {
+        let command = format!(
+            "lsp-show-error %§{} language server doesn't support method {}§",
+            ctx.language_id, method
+        );
+        ctx.exec(meta.clone(), command);
+        return;
+    }
+    let req_params = TextDocumentPositionParams {
+        text_document: TextDocumentIdentifier {
+            uri: Url::from_file_path(&meta.buffile).unwrap(),
+        },
+        position: PositionConverter::new(&meta.buffile, ctx).to_lsp(position),
+    };
+    let id = ctx.next_request_id();
+    ctx.response_waitlist
+        .insert(id.clone(), (meta.clone(), method.into(), params));
+    ctx.call(id, method.into(), req_params);
+}
+
+/// Start a new hierarchy from the prepared items, its nodes are expanded with `method`.
+pub fn editor_prepare_hierarchy(
+    kind: HierarchyKind,
+    method: &str,
+    meta: &EditorMeta,
+    result: Option<Vec<HierarchyItem>>,
+    ctx: &mut Context,
+) {
+    let items = result.unwrap_or_default();
+    if items.is_empty() {
+        let command = format!("info 'No {} hierarchy item under cursor'", kind.name());
+        ctx.exec(meta.clone(), command);
+        return;
+    }
+    *kind.tree(ctx) = Hierarchy {
+        method: method.to_string(),
+        nodes: items.into_iter().map(|item| (0, item)).collect(),
+    };
+    request_children(kind, meta, 0, ctx);
+}
+
+/// Request children of the node on the given line of the hierarchy buffer.
+pub fn hierarchy_expand(
+    kind: HierarchyKind,
+    params: EditorParams,
+    meta: &EditorMeta,
+    ctx: &mut Context,
+) {
+    let params = HierarchyExpandParams::deserialize(params);
+    if params.is_err() {
+        error!("Params should follow HierarchyExpandParams structure");
+        return;
+    }
+    let index = params.unwrap().line as usize - 1;
+    let nodes = &kind.tree(ctx).nodes;
+    if index >= nodes.len() {
+        return;
+    }
+    if nodes.get(index + 1).map(|x| x.0).unwrap_or(0) > nodes[index].0 {
+        ctx.exec(meta.clone(), "info 'Already expanded'".to_string());
+        return;
+    }
+    request_children(kind, meta, index, ctx);
+}
+
+fn request_children(kind: HierarchyKind, meta: &EditorMeta, index: usize, ctx: &mut Context) {
+    let tree = kind.tree(ctx);
+    let method = tree.method.clone();
+    let item = tree.nodes[index].1.clone();
+    let node_params = HierarchyNodeParams {
+        uri: item.uri.to_string(),
+        range: item.range,
+    };
+    let req_params = HierarchyItemParams { item };
+    let id = ctx.next_request_id();
+    ctx.response_waitlist.insert(
+        id.clone(),
+        (
+            meta.clone(),
+            method.clone(),
+            toml::Value::try_from(node_params).unwrap(),
+        ),
+    );
+    ctx.call(id, method, req_params);
+}
+
+/// Insert children below their parent node and render the updated hierarchy.
+pub fn editor_hierarchy_children(
+    kind: HierarchyKind,
+    meta: &EditorMeta,
+    params: &HierarchyNodeParams,
+    items: Vec<HierarchyItem>,
+    ctx: &mut Context,
+) {
+    if items.is_empty() {
+        let command = format!("info 'No {} found'", kind.children_name());
+        ctx.exec(meta.clone(), command);
+        return;
+    }
+    let tree = kind.tree(ctx);
+    // NOTE the same item could appear in the hierarchy more than once, e.g. a function called from
+    // several places, so the first of them which is not expanded yet gets children
+    let is_expanded = |index: usize| {
+        tree.nodes.get(index + 1).map(|x| x.0).unwrap_or(0) > tree.nodes[index].0
+    };
+    let index = (0..tree.nodes.len()).find(|i| {
+        let item = &tree.nodes[*i].1;
+        item.uri.as_str() == params.uri && item.range == params.range && !is_expanded(*i)
+    });
+    let index = match index {
+        Some(index) => index,
+        None => return,
+    };
+    let depth = tree.nodes[index].0 + 1;
+    let children = items
+        .into_iter()
+        .map(|item| (depth, item))
+        .collect::<Vec<_>>();
+    let position = index + 1;
+    tree.nodes.splice(position..position, children);
+    show_hierarchy(kind, meta, index, ctx);
+}
+
+fn show_hierarchy(kind: HierarchyKind, meta: &EditorMeta, index: usize, ctx: &mut Context) {
+    let nodes = match kind {
+        HierarchyKind::Call => &ctx.call_hierarchy.nodes,
+        HierarchyKind::Type => &ctx.type_hierarchy.nodes,
+    };
+    let content = nodes
+        .iter()
+        .map(|(depth, item)| {
+            let filename = item.uri.to_file_path().unwrap();
+            let position = PositionConverter::new(filename.to_str().unwrap(), ctx)
+                .to_kakoune(item.selection_range.start);
+            let filename = filename
+                .strip_prefix(&ctx.root_path)
+                .ok()
+                .and_then(|p| p.to_str())
+                .or_else(|| filename.to_str())
+                .unwrap()
+                .to_string();
+            format!(
+                "{}:{}:{}: {}{:?} {}{}",
+                filename,
+                position.line + 1,
+                position.character + 1,
+                "  ".repeat(*depth),
+                item.kind,
+                item.name,
+                item.detail
+                    .as_ref()
+                    .map(|detail| format!(" {}", detail))
+                    .unwrap_or_default()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let command = format!(
+        "lsp-show-{}-hierarchy %§{}§ %§{}§ %§{}§ {}",
+        kind.name(),
+        ctx.root_path,
+        meta.buffile,
+        content,
+        index + 1
+    );
+    ctx.exec(meta.clone(), command);
+}
@@ -1,6 +1,7 @@
 use context::*;
 use languageserver_types::request::Request;
 use languageserver_types::*;
+use position::PositionConverter;
 use serde::Deserialize;
 use std::str;
 use types::*;
@@ -13,7 +14,7 @@ pub fn text_document_hover(params: EditorParams, meta: &EditorMeta, ctx: &mut Co
         return;
     }
     let req_params = req_params.unwrap();
-    let position = req_params.position;
+    let position = PositionConverter::new(&meta.buffile, ctx).to_lsp(req_params.position);
     let req_params = TextDocumentPositionParams {
         text_document: TextDocumentIdentifier {
             uri: Url::from_file_path(&meta.buffile).unwrap(),
@@ -36,6 +37,7 @@ pub fn editor_hover(
 ) {
     let diagnostics = ctx.diagnostics.get(&meta.buffile);
     let pos = params.position;
+    let lsp_pos = PositionConverter::new(&meta.buffile, ctx).to_lsp(pos);
     let diagnostics = diagnostics
         .and_then(|x| {
             Some(
@@ -43,6 +45,7 @@ pub fn editor_hover(
                     .filter(|x| {
                         let start = x.range.start;
                         let end = x.range.end;
+                        let pos = lsp_pos;
                         (start.line < pos.line && pos.line < end.line)
                             || (start.line == pos.line
                                 && pos.line == end.line
@@ -0,0 +1,266 @@
+use context::*;
+use jsonrpc_core::{self, Params};
+use languageserver_types::*;
+use position::PositionConverter;
+use serde::Deserialize;
+use serde_json::{self, Value};
+use toml;
+use types::*;
+use url::Url;
+
+pub fn text_document_inlay_hint(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    // NOTE hints are requested automatically, so don't bother user if server doesn't support them
+    let supported = ctx
+        .extended_capabilities
+        .inlay_hint_provider
+        .as_ref()
+        .map(|x| x.is_supported())
+        .unwrap_or(false);
+    if !supported {
+        return;
+    }
+    let params = TextDocumentInlayHintParams::deserialize(params);
+    if params.is_err() {
+        error!("Params should follow TextDocumentInlayHintParams structure");
+        return;
+    }
+    let params = params.unwrap();
+    let range = Range::new(
+        Position::new(params.start_line, 0),
+        Position::new(params.end_line + 1, 0),
+    );
+    request_inlay_hints(meta, range, ctx);
+}
+
+fn request_inlay_hints(meta: &EditorMeta, range: Range, ctx: &mut Context) {
+    let req_params = InlayHintParams {
+        text_document: TextDocumentIdentifier {
+            uri: Url::from_file_path(&meta.buffile).unwrap(),
+        },
+        range,
+    };
+    let id = ctx.next_request_id();
+    ctx.response_waitlist.insert(
+        id.clone(),
+        (
+            meta.clone(),
+            INLAY_HINT_METHOD.into(),
+            toml::Value::try_from(range).unwrap(),
+        ),
+    );
+    ctx.call(id, INLAY_HINT_METHOD.into(), req_params);
+}
+
+pub fn editor_inlay_hint(
+    meta: &EditorMeta,
+    range: Range,
+    result: Option<Vec<InlayHint>>,
+    ctx: &mut Context,
+) {
+    let hints = result.unwrap_or_default();
+    let converter = PositionConverter::new(&meta.buffile, ctx);
+    let specs = hints
+        .iter()
+        .map(|hint| {
+            let position = converter.to_kakoune(hint.position);
+            let mut label = label_text(&hint.label);
+            if hint.padding_left.unwrap_or(false) {
+                label.insert(0, ' ');
+            }
+            if hint.padding_right.unwrap_or(false) {
+                label.push(' ');
+            }
+            // NOTE empty range makes replace-ranges insert the label before position
+            format!(
+                "'{}.{}+0|{{InlayHint}}{}'",
+                position.line + 1,
+                position.character + 1,
+                escape(&label.replace("\\", "\\\\").replace("{", "\\{"))
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    ctx.inlay_hints
+        .insert(meta.buffile.clone(), InlayHintsCache { range, hints });
+    let command = format!(
+        "eval -buffer %§{}§ %§set buffer lsp_inlay_hints {} {}§",
+        meta.buffile, meta.version, specs
+    );
+    ctx.exec(meta.clone(), command);
+}
+
+/// Re-request inlay hints for all buffers which have them.
+pub fn inlay_hint_refresh(
+    _params: Params,
+    ctx: &mut Context,
+) -> Result<Value, jsonrpc_core::Error> {
+    let buffers = ctx
+        .inlay_hints
+        .iter()
+        .filter_map(|(buffile, cache)| {
+            ctx.versions
+                .get(buffile)
+                .map(|version| (buffile.clone(), *version, cache.range))
+        })
+        .collect::<Vec<_>>();
+    for (buffile, version, range) in buffers {
+        let meta = EditorMeta {
+            session: ctx.session.clone(),
+            client: None,
+            buffile,
+            version,
+        };
+        request_inlay_hints(&meta, range, ctx);
+    }
+    Ok(Value::Null)
+}
+
+/// Show tooltips of inlay hints on the given line, resolving them first if needed.
+pub fn inlay_hint_info(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    let params = PositionParams::deserialize(params);
+    if params.is_err() {
+        error!("Params should follow PositionParams structure");
+        return;
+    }
+    let line = params.unwrap().position.line;
+    let resolve_provider = ctx
+        .extended_capabilities
+        .inlay_hint_provider
+        .as_ref()
+        .map(|x| x.resolve_provider())
+        .unwrap_or(false);
+    let unresolved = ctx
+        .inlay_hints
+        .get(&meta.buffile)
+        .map(|cache| {
+            cache
+                .hints
+                .iter()
+                .filter(|x| x.position.line == line && x.tooltip.is_none())
+                .map(|x| serde_json::to_value(x).unwrap())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if !resolve_provider || unresolved.is_empty() {
+        show_inlay_hint_info(meta, line, ctx);
+        return;
+    }
+    let batch_id = ctx.start_batch(unresolved.len(), meta.version);
+    let batch_params = toml::Value::try_from(InlayHintResolveParams { batch_id, line }).unwrap();
+    for hint in unresolved {
+        let id = ctx.next_request_id();
+        ctx.response_waitlist.insert(
+            id.clone(),
+            (
+                meta.clone(),
+                INLAY_HINT_RESOLVE_METHOD.into(),
+                batch_params.clone(),
+            ),
+        );
+        ctx.call(id, INLAY_HINT_RESOLVE_METHOD.into(), hint);
+    }
+}
+
+pub fn editor_inlay_hint_resolve(
+    meta: &EditorMeta,
+    params: &InlayHintResolveParams,
+    result: Value,
+    ctx: &mut Context,
+) {
+    if let Some(batch) = ctx.add_to_batch(params.batch_id, result) {
+        let resolved = batch
+            .responses
+            .into_iter()
+            .filter_map(|result| serde_json::from_value::<InlayHint>(result).ok())
+            .collect::<Vec<_>>();
+        if let Some(cache) = ctx.inlay_hints.get_mut(&meta.buffile) {
+            // NOTE responses could arrive in any order, so we replace all unresolved hints on the
+            // line rather than tracking each of them
+            cache
+                .hints
+                .retain(|x| x.position.line != params.line || x.tooltip.is_some());
+            cache.hints.extend(resolved);
+        }
+        show_inlay_hint_info(meta, params.line, ctx);
+    }
+}
+
+fn show_inlay_hint_info(meta: &EditorMeta, line: u64, ctx: &mut Context) {
+    let mut hints = ctx
+        .inlay_hints
+        .get(&meta.buffile)
+        .map(|cache| {
+            cache
+                .hints
+                .iter()
+                .filter(|x| x.position.line == line)
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    hints.sort_by_key(|x| x.position.character);
+    let content = hints
+        .iter()
+        .filter_map(|hint| {
+            let tooltip = hint_tooltip(hint)?;
+            Some(format!("{}\n{}", label_text(&hint.label).trim(), tooltip))
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if content.is_empty() {
+        ctx.exec(
+            meta.clone(),
+            "info 'No inlay hint tooltips on this line'".to_string(),
+        );
+        return;
+    }
+    ctx.exec(meta.clone(), format!("info '{}'", escape(&content)));
+}
+
+fn label_text(label: &InlayHintLabel) -> String {
+    match label {
+        InlayHintLabel::String(label) => label.clone(),
+        InlayHintLabel::Parts(parts) => parts
+            .iter()
+            .map(|x| x.value.as_str())
+            .collect::<Vec<_>>()
+            .join(""),
+    }
+}
+
+/// Return tooltip of the hint or tooltips of its label parts.
+fn hint_tooltip(hint: &InlayHint) -> Option<String> {
+    if let Some(tooltip) = hint.tooltip.as_ref().and_then(tooltip_text) {
+        return Some(tooltip);
+    }
+    match hint.label {
+        InlayHintLabel::Parts(ref parts) => {
+            let tooltips = parts
+                .iter()
+                .filter_map(|x| x.tooltip.as_ref().and_then(tooltip_text))
+                .collect::<Vec<_>>();
+            if tooltips.is_empty() {
+                None
+            } else {
+                Some(tooltips.join("\n"))
+            }
+        }
+        InlayHintLabel::String(_) => None,
+    }
+}
+
+/// Tooltip is either a plain string or a markup content.
+fn tooltip_text(tooltip: &Value) -> Option<String> {
+    match tooltip {
+        Value::String(text) => Some(text.clone()),
+        Value::Object(markup) => markup
+            .get("value")
+            .and_then(|x| x.as_str())
+            .map(|x| x.to_string()),
+        _ => None,
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace("'", "''")
+}
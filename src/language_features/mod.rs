@@ -1,8 +1,22 @@
+pub mod call_hierarchy;
+pub mod code_action;
+pub mod code_lens;
 pub mod completion;
 pub mod cquery;
 pub mod definition;
+pub mod document_color;
+pub mod document_highlight;
+pub mod document_link;
 pub mod document_symbol;
+pub mod folding_range;
 pub mod formatting;
+pub mod hierarchy;
 pub mod hover;
+pub mod inlay_hint;
 pub mod references;
+pub mod rename;
+pub mod selection_range;
+pub mod semantic_tokens;
 pub mod signature_help;
+pub mod type_hierarchy;
+pub mod workspace_symbol;
@@ -2,6 +2,8 @@ use context::*;
 use itertools::Itertools;
 use languageserver_types::request::Request;
 use languageserver_types::*;
+use position::character_to_byte;
+use position::PositionConverter;
 use serde::Deserialize;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
@@ -15,7 +17,7 @@ pub fn text_document_references(params: EditorParams, meta: &EditorMeta, ctx: &m
         return;
     }
     let req_params = req_params.unwrap();
-    let position = req_params.position;
+    let position = PositionConverter::new(&meta.buffile, ctx).to_lsp(req_params.position);
     let req_params = ReferenceParams {
         text_document: TextDocumentIdentifier {
             uri: Url::from_file_path(&meta.buffile).unwrap(),
@@ -39,76 +41,11 @@ pub fn editor_references(
     result: ReferencesResponse,
     ctx: &mut Context,
 ) {
-    if let Some(mut locations) = match result {
+    if let Some(locations) = match result {
         ReferencesResponse::Array(locations) => Some(locations),
         ReferencesResponse::None => None,
     } {
-        // Sort locations by (filename, line)
-        locations.sort_unstable_by_key(|location| {
-            (location.uri.to_file_path(), location.range.start.line)
-        });
-
-        let content = locations
-            .iter()
-            .group_by(|location| location.uri.to_file_path())
-            .into_iter()
-            .map(|(filename, group)| {
-                let filename = filename.unwrap();
-                let file = File::open(&filename);
-                let name = filename
-                    .strip_prefix(&ctx.root_path)
-                    .ok()
-                    .and_then(|p| Some(p.to_str().unwrap()))
-                    .or_else(|| filename.to_str())
-                    .unwrap();
-
-                if file.is_err() {
-                    error!("Failed to open referenced file: {}", name);
-                    return group
-                        .map(|_loc| String::new())
-                        .collect::<Vec<String>>()
-                        .join("\n");
-                }
-                let mut buffer = BufReader::new(file.unwrap()).lines();
-                let mut next_buf_line = 0;
-                return group
-                    .map(|location| {
-                        let p = location.range.start;
-                        let loc_line = p.line as usize;
-                        while next_buf_line != loc_line {
-                            buffer.next();
-                            next_buf_line += 1;
-                        }
-                        next_buf_line += 1;
-                        match buffer.next() {
-                            Some(Ok(line)) => {
-                                return format!(
-                                    "{}:{}:{}:{}",
-                                    name,
-                                    p.line + 1,
-                                    p.character + 1,
-                                    line
-                                )
-                            }
-                            Some(Err(e)) => {
-                                error!("Failed to read line {} in {}: {}", name, loc_line, e);
-                                return String::new();
-                            }
-                            None => {
-                                error!(
-                                    "End of file reached, line {} not found in {}",
-                                    loc_line, name,
-                                );
-                                return String::new();
-                            }
-                        }
-                    })
-                    .collect::<Vec<String>>()
-                    .join("\n");
-            })
-            .collect::<Vec<String>>()
-            .join("\n");
-
+        let content = format_locations(locations, ctx);
         let command = format!(
             "lsp-show-references %§{}§ %§{}§",
             ctx.root_path, content,
@@ -116,3 +53,68 @@ pub fn editor_references(
         ctx.exec(meta.clone(), command);
     };
 }
+
+/// Render locations as grep-like lines with paths relative to the project root.
+pub fn format_locations(mut locations: Vec<Location>, ctx: &Context) -> String {
+    // Sort locations by (filename, line)
+    locations.sort_unstable_by_key(|location| {
+        (location.uri.to_file_path(), location.range.start.line)
+    });
+
+    locations
+        .iter()
+        .group_by(|location| location.uri.to_file_path())
+        .into_iter()
+        .map(|(filename, group)| {
+            let filename = filename.unwrap();
+            let file = File::open(&filename);
+            let name = filename
+                .strip_prefix(&ctx.root_path)
+                .ok()
+                .and_then(|p| Some(p.to_str().unwrap()))
+                .or_else(|| filename.to_str())
+                .unwrap();
+
+            if file.is_err() {
+                error!("Failed to open referenced file: {}", name);
+                return group
+                    .map(|_loc| String::new())
+                    .collect::<Vec<String>>()
+                    .join("\n");
+            }
+            let mut buffer = BufReader::new(file.unwrap()).lines();
+            let mut next_buf_line = 0;
+            return group
+                .map(|location| {
+                    let p = location.range.start;
+                    let loc_line = p.line as usize;
+                    while next_buf_line != loc_line {
+                        buffer.next();
+                        next_buf_line += 1;
+                    }
+                    next_buf_line += 1;
+                    match buffer.next() {
+                        Some(Ok(line)) => {
+                            let column =
+                                character_to_byte(&line, p.character, ctx.position_encoding);
+                            return format!("{}:{}:{}:{}", name, p.line + 1, column + 1, line);
+                        }
+                        Some(Err(e)) => {
+                            error!("Failed to read line {} in {}: {}", name, loc_line, e);
+                            return String::new();
+                        }
+                        None => {
+                            error!(
+                                "End of file reached, line {} not found in {}",
+                                loc_line, name,
+                            );
+                            return String::new();
+                        }
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join("\n");
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
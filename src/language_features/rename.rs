@@ -0,0 +1,47 @@
+use context::*;
+use languageserver_types::request::Request;
+use languageserver_types::*;
+use position::PositionConverter;
+use serde::Deserialize;
+use types::*;
+use url::Url;
+use workspace;
+
+pub fn text_document_rename(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    let req_params = TextDocumentRenameParams::deserialize(params.clone());
+    if req_params.is_err() {
+        error!("Params should follow TextDocumentRenameParams structure");
+        return;
+    }
+    let req_params = req_params.unwrap();
+    let req_params = RenameParams {
+        text_document: TextDocumentIdentifier {
+            uri: Url::from_file_path(&meta.buffile).unwrap(),
+        },
+        position: PositionConverter::new(&meta.buffile, ctx).to_lsp(req_params.position),
+        new_name: req_params.new_name,
+    };
+    let id = ctx.next_request_id();
+    ctx.response_waitlist.insert(
+        id.clone(),
+        (meta.clone(), request::Rename::METHOD.into(), params),
+    );
+    ctx.call(id, request::Rename::METHOD.into(), req_params);
+}
+
+pub fn editor_rename(
+    meta: &EditorMeta,
+    _params: &TextDocumentRenameParams,
+    result: Option<WorkspaceEdit>,
+    ctx: &mut Context,
+) {
+    if result.is_none() {
+        return;
+    }
+    if !workspace::apply_edit(meta, result.unwrap(), ctx) {
+        ctx.exec(
+            meta.clone(),
+            "lsp-show-error 'Failed to apply some of rename edits'".to_string(),
+        );
+    }
+}
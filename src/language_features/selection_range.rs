@@ -0,0 +1,163 @@
+use context::*;
+use languageserver_types::*;
+use position::PositionConverter;
+use serde::Deserialize;
+use types::*;
+use url::Url;
+use util::{lsp_range_to_kakoune, parse_kakoune_range};
+
+pub fn text_document_selection_range(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    if !ctx
+        .extended_capabilities
+        .selection_range_provider
+        .is_supported()
+    {
+        let command = format!(
+            "lsp-show-error %§{} language server doesn't support method {}§",
+            ctx.language_id, SELECTION_RANGE_METHOD
+        );
+        ctx.exec(meta.clone(), command);
+        return;
+    }
+    let selections = parse_selections(params.clone(), meta, ctx);
+    if selections.is_none() {
+        return;
+    }
+    let req_params = SelectionRangeParams {
+        text_document: TextDocumentIdentifier {
+            uri: Url::from_file_path(&meta.buffile).unwrap(),
+        },
+        positions: selections.unwrap().iter().map(|x| x.start).collect(),
+    };
+    let id = ctx.next_request_id();
+    ctx.response_waitlist.insert(
+        id.clone(),
+        (meta.clone(), SELECTION_RANGE_METHOD.into(), params),
+    );
+    ctx.call(id, SELECTION_RANGE_METHOD.into(), req_params);
+}
+
+pub fn editor_selection_range(
+    meta: &EditorMeta,
+    params: EditorParams,
+    result: Option<Vec<SelectionRange>>,
+    ctx: &mut Context,
+) {
+    let selections = parse_selections(params, meta, ctx);
+    if selections.is_none() {
+        return;
+    }
+    let current = current_selections(meta, selections.unwrap(), ctx);
+    let result = result.unwrap_or_default();
+    let expanded = current
+        .iter()
+        .enumerate()
+        .map(|(i, selection)| {
+            // NOTE selection ranges go from innermost to outermost, so the first one which is
+            // bigger than selection is the next step of expansion
+            let mut selection_range = result.get(i);
+            while let Some(x) = selection_range {
+                if x.range != *selection
+                    && x.range.start <= selection.start
+                    && selection.end <= x.range.end
+                {
+                    return x.range;
+                }
+                selection_range = x.parent.as_deref();
+            }
+            *selection
+        })
+        .collect::<Vec<_>>();
+    if expanded == current {
+        ctx.exec(meta.clone(), "info 'Nothing to expand'".to_string());
+        return;
+    }
+    select(meta, &expanded, ctx);
+    ctx.selection_range_history
+        .get_mut(&meta.buffile)
+        .unwrap()
+        .steps
+        .push((current, expanded));
+}
+
+pub fn selection_range_shrink(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    let selections = parse_selections(params, meta, ctx);
+    if selections.is_none() {
+        return;
+    }
+    current_selections(meta, selections.unwrap(), ctx);
+    let step = ctx
+        .selection_range_history
+        .get_mut(&meta.buffile)
+        .unwrap()
+        .steps
+        .pop();
+    match step {
+        Some((previous, _)) => select(meta, &previous, ctx),
+        None => ctx.exec(meta.clone(), "info 'Nothing to shrink'".to_string()),
+    }
+}
+
+/// Parse selections of the editor and convert them into LSP ranges.
+fn parse_selections(params: EditorParams, meta: &EditorMeta, ctx: &Context) -> Option<Vec<Range>> {
+    let params = TextDocumentSelectionRangeParams::deserialize(params);
+    if params.is_err() {
+        error!("Params should follow TextDocumentSelectionRangeParams structure");
+        return None;
+    }
+    let params = params.unwrap();
+    let converter = PositionConverter::new(&meta.buffile, ctx);
+    let selections = params
+        .ranges
+        .iter()
+        .map(|desc| parse_kakoune_range(desc).map(|x| converter.range_to_lsp(x)))
+        .collect::<Option<Vec<_>>>();
+    if selections.is_none() {
+        error!("Failed to parse selections {:?}", params.ranges);
+    }
+    selections
+}
+
+/// Return selections as they were set by the last expansion if they weren't changed since then,
+/// otherwise start history of expansions from the scratch.
+fn current_selections(meta: &EditorMeta, selections: Vec<Range>, ctx: &mut Context) -> Vec<Range> {
+    let history = ctx
+        .selection_range_history
+        .entry(meta.buffile.clone())
+        .or_default();
+    // NOTE range conversion is not reversible for ranges ending at line end, so we compare only
+    // starts of selections and take the rest from history
+    let last = history
+        .steps
+        .last()
+        .map(|(_, expanded)| expanded.clone())
+        .filter(|expanded| {
+            history.version == meta.version
+                && expanded.len() == selections.len()
+                && expanded
+                    .iter()
+                    .zip(selections.iter())
+                    .all(|(x, y)| x.start == y.start)
+        });
+    match last {
+        Some(expanded) => expanded,
+        None => {
+            history.version = meta.version;
+            history.steps.clear();
+            selections
+        }
+    }
+}
+
+fn select(meta: &EditorMeta, ranges: &[Range], ctx: &mut Context) {
+    let converter = PositionConverter::new(&meta.buffile, ctx);
+    let command = format!(
+        "select {}",
+        ranges
+            .iter()
+            .map(|x| lsp_range_to_kakoune(&converter.range_to_kakoune(*x)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+    ctx.exec(meta.clone(), command);
+}
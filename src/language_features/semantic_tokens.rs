@@ -0,0 +1,201 @@
+use context::*;
+use jsonrpc_core::{self, Params};
+use languageserver_types::*;
+use position::PositionConverter;
+use serde_json::{self, Value};
+use toml;
+use types::*;
+use url::Url;
+
+/// Request semantic tokens for the buffer, deltas are requested when possible.
+pub fn text_document_semantic_tokens(meta: &EditorMeta, line_count: u64, ctx: &mut Context) {
+    let provider = ctx.extended_capabilities.semantic_tokens_provider.clone();
+    if provider.is_none() {
+        return;
+    }
+    let provider = provider.unwrap();
+    let text_document = TextDocumentIdentifier {
+        uri: Url::from_file_path(&meta.buffile).unwrap(),
+    };
+    let cache = ctx.semantic_tokens.entry(meta.buffile.clone()).or_default();
+    cache.line_count = line_count;
+    let previous_result_id = cache.result_id.clone();
+    let (method, req_params) = match provider.full {
+        SemanticTokensFullOptions::Options { delta: Some(true) }
+            if previous_result_id.is_some() =>
+        {
+            (
+                SEMANTIC_TOKENS_FULL_DELTA_METHOD,
+                serde_json::to_value(SemanticTokensDeltaParams {
+                    text_document,
+                    previous_result_id: previous_result_id.unwrap(),
+                }),
+            )
+        }
+        SemanticTokensFullOptions::Bool(false) if provider.range.is_supported() => (
+            SEMANTIC_TOKENS_RANGE_METHOD,
+            serde_json::to_value(SemanticTokensRangeParams {
+                text_document,
+                range: Range::new(Position::new(0, 0), Position::new(line_count, 0)),
+            }),
+        ),
+        SemanticTokensFullOptions::Bool(false) => return,
+        _ => (
+            SEMANTIC_TOKENS_FULL_METHOD,
+            serde_json::to_value(SemanticTokensParams { text_document }),
+        ),
+    };
+    let id = ctx.next_request_id();
+    ctx.response_waitlist.insert(
+        id.clone(),
+        (
+            meta.clone(),
+            method.into(),
+            toml::Value::Table(toml::value::Table::default()),
+        ),
+    );
+    ctx.call(id, method.into(), req_params.unwrap());
+}
+
+pub fn editor_semantic_tokens_full(
+    meta: &EditorMeta,
+    result: Option<SemanticTokens>,
+    ctx: &mut Context,
+) {
+    let result = result.unwrap_or(SemanticTokens {
+        result_id: None,
+        data: vec![],
+    });
+    update_cache(meta, result, ctx);
+}
+
+pub fn editor_semantic_tokens_full_delta(
+    meta: &EditorMeta,
+    result: Option<SemanticTokensFullDeltaResult>,
+    ctx: &mut Context,
+) {
+    match result {
+        Some(SemanticTokensFullDeltaResult::Tokens(tokens)) => update_cache(meta, tokens, ctx),
+        Some(SemanticTokensFullDeltaResult::Delta(delta)) => {
+            let cache = ctx.semantic_tokens.entry(meta.buffile.clone()).or_default();
+            let mut edits = delta.edits;
+            // NOTE edits refer to the previous data, applying them from the end keeps offsets valid
+            edits.sort_by_key(|x| x.start);
+            for edit in edits.into_iter().rev() {
+                let start = edit.start.min(cache.data.len());
+                let end = (edit.start + edit.delete_count).min(cache.data.len());
+                cache.data.splice(start..end, edit.data.unwrap_or_default());
+            }
+            cache.result_id = delta.result_id;
+            let data = cache.data.clone();
+            show_semantic_tokens(meta, &data, ctx);
+        }
+        None => update_cache(
+            meta,
+            SemanticTokens {
+                result_id: None,
+                data: vec![],
+            },
+            ctx,
+        ),
+    }
+}
+
+pub fn editor_semantic_tokens_range(
+    meta: &EditorMeta,
+    result: Option<SemanticTokens>,
+    ctx: &mut Context,
+) {
+    if let Some(result) = result {
+        show_semantic_tokens(meta, &result.data, ctx);
+    }
+}
+
+fn update_cache(meta: &EditorMeta, tokens: SemanticTokens, ctx: &mut Context) {
+    show_semantic_tokens(meta, &tokens.data, ctx);
+    let cache = ctx.semantic_tokens.entry(meta.buffile.clone()).or_default();
+    cache.result_id = tokens.result_id;
+    cache.data = tokens.data;
+}
+
+/// Re-request semantic tokens for all open buffers.
+pub fn semantic_tokens_refresh(
+    _params: Params,
+    ctx: &mut Context,
+) -> Result<Value, jsonrpc_core::Error> {
+    // NOTE buffers which have never got tokens are skipped, as their size is unknown
+    let buffers = ctx
+        .versions
+        .iter()
+        .filter_map(|(buffile, version)| {
+            ctx.semantic_tokens
+                .get(buffile)
+                .map(|x| (buffile.clone(), *version, x.line_count))
+        })
+        .collect::<Vec<_>>();
+    for (buffile, version, line_count) in buffers {
+        let meta = EditorMeta {
+            session: ctx.session.clone(),
+            client: None,
+            buffile,
+            version,
+        };
+        text_document_semantic_tokens(&meta, line_count, ctx);
+    }
+    Ok(Value::Null)
+}
+
+fn show_semantic_tokens(meta: &EditorMeta, data: &[u64], ctx: &mut Context) {
+    let legend = match ctx.extended_capabilities.semantic_tokens_provider {
+        Some(ref provider) => &provider.legend,
+        None => return,
+    };
+    let faces = &ctx.config.semantic_tokens;
+    let converter = PositionConverter::new(&meta.buffile, ctx);
+    let mut line = 0;
+    let mut start = 0;
+    let ranges = data
+        .chunks(5)
+        .filter(|token| token.len() == 5)
+        .filter_map(|token| {
+            // NOTE tokens are encoded relative to the previous one: line is a delta and start is
+            // a delta only if the token is on the same line as the previous one
+            if token[0] > 0 {
+                line += token[0];
+                start = token[1];
+            } else {
+                start += token[1];
+            }
+            let length = token[2];
+            if length == 0 {
+                return None;
+            }
+            let token_type = legend.token_types.get(token[3] as usize)?;
+            // NOTE face for the first modifier found in the table wins over face for the type
+            let face = legend
+                .token_modifiers
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i < 64 && token[4] & (1 << i) != 0)
+                .filter_map(|(_, modifier)| faces.get(&format!("{}.{}", token_type, modifier)))
+                .next()
+                .or_else(|| faces.get(token_type))?;
+            let token_start = converter.to_kakoune(Position::new(line, start));
+            let token_end = converter.to_kakoune(Position::new(line, start + length));
+            Some(format!(
+                "{}.{},{}.{}|{}",
+                line + 1,
+                token_start.character + 1,
+                line + 1,
+                token_end.character,
+                face
+            ))
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    let command = format!(
+        "eval -buffer %§{}§ %§set buffer lsp_semantic_tokens {} {}§",
+        meta.buffile, meta.version, ranges
+    );
+    ctx.exec(meta.clone(), command);
+}
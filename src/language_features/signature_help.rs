@@ -1,6 +1,7 @@
 use context::*;
 use languageserver_types::request::Request;
 use languageserver_types::*;
+use position::PositionConverter;
 use serde::Deserialize;
 use types::*;
 use url::Url;
@@ -12,7 +13,7 @@ pub fn text_document_signature_help(params: EditorParams, meta: &EditorMeta, ctx
         return;
     }
     let req_params = req_params.unwrap();
-    let position = req_params.position;
+    let position = PositionConverter::new(&meta.buffile, ctx).to_lsp(req_params.position);
     let req_params = TextDocumentPositionParams {
         text_document: TextDocumentIdentifier {
             uri: Url::from_file_path(&meta.buffile).unwrap(),
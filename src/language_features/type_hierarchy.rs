@@ -0,0 +1,47 @@
+use context::*;
+use language_features::hierarchy::{self, HierarchyKind};
+use serde::Deserialize;
+use types::*;
+
+pub fn text_document_prepare_type_hierarchy(
+    params: EditorParams,
+    meta: &EditorMeta,
+    ctx: &mut Context,
+) {
+    let type_hierarchy_params = TextDocumentTypeHierarchyParams::deserialize(params.clone());
+    if type_hierarchy_params.is_err() {
+        error!("Params should follow TextDocumentTypeHierarchyParams structure");
+        return;
+    }
+    let position = type_hierarchy_params.unwrap().position;
+    hierarchy::text_document_prepare_hierarchy(HierarchyKind::Type, position, params, meta, ctx);
+}
+
+pub fn editor_prepare_type_hierarchy(
+    meta: &EditorMeta,
+    params: &TextDocumentTypeHierarchyParams,
+    result: Option<Vec<HierarchyItem>>,
+    ctx: &mut Context,
+) {
+    let method = if params.supertypes {
+        SUPERTYPES_METHOD
+    } else {
+        SUBTYPES_METHOD
+    };
+    hierarchy::editor_prepare_hierarchy(HierarchyKind::Type, method, meta, result, ctx);
+}
+
+/// Request supertypes or subtypes of the node on the given line of the type hierarchy buffer.
+pub fn type_hierarchy_expand(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    hierarchy::hierarchy_expand(HierarchyKind::Type, params, meta, ctx);
+}
+
+pub fn editor_types(
+    meta: &EditorMeta,
+    params: &HierarchyNodeParams,
+    result: Option<Vec<HierarchyItem>>,
+    ctx: &mut Context,
+) {
+    let items = result.unwrap_or_default();
+    hierarchy::editor_hierarchy_children(HierarchyKind::Type, meta, params, items, ctx);
+}
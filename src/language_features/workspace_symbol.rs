@@ -0,0 +1,36 @@
+use context::*;
+use language_features::document_symbol::format_symbol_information;
+use languageserver_types::request::Request;
+use languageserver_types::*;
+use serde::Deserialize;
+use types::*;
+
+pub fn workspace_symbol(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    let req_params = WorkspaceSymbolParams::deserialize(params.clone());
+    if req_params.is_err() {
+        error!("Params should follow WorkspaceSymbolParams structure");
+        return;
+    }
+    let req_params = req_params.unwrap();
+    let id = ctx.next_request_id();
+    ctx.response_waitlist.insert(
+        id.clone(),
+        (meta.clone(), request::WorkspaceSymbol::METHOD.into(), params),
+    );
+    ctx.call(id, request::WorkspaceSymbol::METHOD.into(), req_params);
+}
+
+pub fn editor_workspace_symbol(
+    meta: &EditorMeta,
+    result: Option<Vec<SymbolInformation>>,
+    ctx: &mut Context,
+) {
+    // NOTE empty result is rendered as well to not leave stale symbols from the previous query
+    // when symbols are requested incrementally
+    let content = format_symbol_information(result.unwrap_or_default(), ctx);
+    let command = format!(
+        "lsp-show-workspace-symbol %§{}§ %§{}§",
+        ctx.root_path, content,
+    );
+    ctx.exec(meta.clone(), command);
+}
@@ -33,10 +33,14 @@ mod editor_transport;
 mod general;
 mod language_features;
 mod language_server_transport;
+mod position;
 mod project_root;
+mod snippet;
+mod text_edit;
 mod text_sync;
 mod types;
 mod util;
+mod workspace;
 
 use clap::{App, Arg};
 use daemonize::Daemonize;
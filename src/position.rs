@@ -0,0 +1,117 @@
+use context::*;
+use languageserver_types::{Position, Range};
+use std::borrow::Cow;
+use std::fs;
+use types::*;
+
+/// Converts positions between Kakoune and language server. Kakoune counts columns in bytes while
+/// language server counts characters in units of the negotiated position encoding.
+/// Both positions are 0-based, Kakoune ones are adjusted to 1-based by callers as before.
+pub struct PositionConverter<'a> {
+    encoding: PositionEncoding,
+    text: Cow<'a, str>,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> PositionConverter<'a> {
+    /// Create converter for the file. Text of buffers is taken as it was last synced with language
+    /// server, other files are read from disk. If text is not available then positions are passed
+    /// as is.
+    pub fn new(buffile: &str, ctx: &'a Context) -> Self {
+        let encoding = ctx.position_encoding;
+        let text = if encoding == PositionEncoding::Utf8 {
+            // NOTE byte columns are what language server expects, no need to look at the text
+            Cow::Borrowed("")
+        } else if let Some(text) = ctx.documents.get(buffile) {
+            Cow::Borrowed(text.as_str())
+        } else {
+            Cow::Owned(fs::read_to_string(buffile).unwrap_or_default())
+        };
+        let line_starts = if text.is_empty() {
+            vec![]
+        } else {
+            let mut line_starts = vec![0];
+            line_starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+            line_starts
+        };
+        PositionConverter {
+            encoding,
+            text,
+            line_starts,
+        }
+    }
+
+    fn line(&self, line: u64) -> Option<&str> {
+        let line = line as usize;
+        let start = *self.line_starts.get(line)?;
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .map(|x| x - 1)
+            .unwrap_or_else(|| self.text.len());
+        Some(&self.text[start..end])
+    }
+
+    /// Convert position with byte column into language server position.
+    pub fn to_lsp(&self, position: Position) -> Position {
+        match self.line(position.line) {
+            Some(line) => Position::new(
+                position.line,
+                byte_to_character(line, position.character, self.encoding),
+            ),
+            None => position,
+        }
+    }
+
+    /// Convert language server position into position with byte column.
+    pub fn to_kakoune(&self, position: Position) -> Position {
+        match self.line(position.line) {
+            Some(line) => Position::new(
+                position.line,
+                character_to_byte(line, position.character, self.encoding),
+            ),
+            None => position,
+        }
+    }
+
+    pub fn range_to_lsp(&self, range: Range) -> Range {
+        Range::new(self.to_lsp(range.start), self.to_lsp(range.end))
+    }
+
+    pub fn range_to_kakoune(&self, range: Range) -> Range {
+        Range::new(self.to_kakoune(range.start), self.to_kakoune(range.end))
+    }
+}
+
+fn char_len(c: char, encoding: PositionEncoding) -> u64 {
+    match encoding {
+        PositionEncoding::Utf8 => c.len_utf8() as u64,
+        PositionEncoding::Utf16 => c.len_utf16() as u64,
+        PositionEncoding::Utf32 => 1,
+    }
+}
+
+/// Convert byte column on the line into character offset in the given encoding.
+pub fn byte_to_character(line: &str, column: u64, encoding: PositionEncoding) -> u64 {
+    let mut character = 0;
+    for (i, c) in line.char_indices() {
+        if i as u64 >= column {
+            return character;
+        }
+        character += char_len(c, encoding);
+    }
+    // NOTE columns past the end of line are kept as is, e.g. end of line markers
+    character + column.saturating_sub(line.len() as u64)
+}
+
+/// Convert character offset in the given encoding into byte column on the line.
+pub fn character_to_byte(line: &str, character: u64, encoding: PositionEncoding) -> u64 {
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= character {
+            return i as u64;
+        }
+        units += char_len(c, encoding);
+    }
+    line.len() as u64 + character.saturating_sub(units)
+}
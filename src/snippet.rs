@@ -0,0 +1,311 @@
+use std::path::Path;
+
+/// Snippet expanded into plain text with placeholders given as byte ranges in that text.
+#[derive(Debug)]
+pub struct Snippet {
+    pub text: String,
+    /// Placeholders in the order of navigation, i.e. by tabstop number with `$0` being the last.
+    pub placeholders: Vec<(usize, usize)>,
+}
+
+/// Expand snippet following LSP snippet grammar. Choices are expanded into their first option,
+/// variables are resolved relative to `buffile` and unknown ones are replaced by their default.
+/// Malformed constructs are inserted literally.
+pub fn expand(snippet: &str, buffile: &str) -> Snippet {
+    let mut parser = Parser {
+        chars: snippet.chars().collect(),
+        pos: 0,
+        text: String::new(),
+        tabstops: vec![],
+        buffile,
+    };
+    parser.parse_any(false);
+    let mut tabstops = parser.tabstops;
+    // NOTE $0 denotes the final cursor position, so it goes after all other tabstops
+    tabstops.sort_by_key(|&(number, start, _)| (number == 0, number, start));
+    // NOTE only the first occurrence of a tabstop is navigated to, mirrors are left as is
+    tabstops.dedup_by_key(|&mut (number, _, _)| number);
+    Snippet {
+        text: parser.text,
+        placeholders: tabstops
+            .into_iter()
+            .map(|(_, start, end)| (start, end))
+            .collect(),
+    }
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    text: String,
+    // NOTE tabstop number and byte range in the expanded text
+    tabstops: Vec<(u64, usize, usize)>,
+    buffile: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Parse text and nested constructs until the end of input or, when `nested` is true, until
+    /// the closing brace. Returns false if the closing brace was expected but not found.
+    fn parse_any(&mut self, nested: bool) -> bool {
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '}' if nested => return true,
+                '\\' => match self.peek() {
+                    Some(next) if next == '$' || next == '}' || next == '\\' => {
+                        self.pos += 1;
+                        self.text.push(next);
+                    }
+                    _ => self.text.push(c),
+                },
+                '$' => {
+                    let start = self.pos;
+                    let text_len = self.text.len();
+                    let tabstops_len = self.tabstops.len();
+                    if !self.parse_dollar() {
+                        self.pos = start;
+                        self.text.truncate(text_len);
+                        self.tabstops.truncate(tabstops_len);
+                        self.text.push('$');
+                    }
+                }
+                _ => self.text.push(c),
+            }
+        }
+        !nested
+    }
+
+    fn parse_int(&mut self) -> Option<u64> {
+        let start = self.pos;
+        while self.peek().map(|c| c.is_ascii_digit()).unwrap_or(false) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .ok()
+    }
+
+    fn parse_var(&mut self) -> Option<String> {
+        let start = self.pos;
+        match self.peek() {
+            Some(c) if c == '_' || c.is_ascii_alphabetic() => (),
+            _ => return None,
+        }
+        while self
+            .peek()
+            .map(|c| c == '_' || c.is_ascii_alphanumeric())
+            .unwrap_or(false)
+        {
+            self.pos += 1;
+        }
+        Some(self.chars[start..self.pos].iter().collect())
+    }
+
+    /// Parse construct following `$`. Returns false if it's malformed.
+    fn parse_dollar(&mut self) -> bool {
+        if let Some(number) = self.parse_int() {
+            let offset = self.text.len();
+            self.tabstops.push((number, offset, offset));
+            return true;
+        }
+        if let Some(name) = self.parse_var() {
+            let value = self.resolve_variable(&name).unwrap_or_default();
+            self.text.push_str(&value);
+            return true;
+        }
+        if !self.eat('{') {
+            return false;
+        }
+        if let Some(number) = self.parse_int() {
+            let start = self.text.len();
+            if self.eat('}') {
+                self.tabstops.push((number, start, start));
+                return true;
+            }
+            if self.eat(':') {
+                if !self.parse_any(true) {
+                    return false;
+                }
+                let end = self.text.len();
+                self.tabstops.push((number, start, end));
+                return true;
+            }
+            if self.eat('|') {
+                let choice = match self.parse_choice() {
+                    Some(choice) => choice,
+                    None => return false,
+                };
+                self.text.push_str(&choice);
+                let end = self.text.len();
+                self.tabstops.push((number, start, end));
+                return true;
+            }
+            return false;
+        }
+        let name = match self.parse_var() {
+            Some(name) => name,
+            None => return false,
+        };
+        let value = self.resolve_variable(&name);
+        if self.eat('}') {
+            self.text.push_str(&value.unwrap_or_default());
+            return true;
+        }
+        if self.eat(':') {
+            let text_len = self.text.len();
+            let tabstops_len = self.tabstops.len();
+            if !self.parse_any(true) {
+                return false;
+            }
+            // NOTE default is used only when variable is unknown
+            if let Some(value) = value {
+                self.text.truncate(text_len);
+                self.tabstops.truncate(tabstops_len);
+                self.text.push_str(&value);
+            }
+            return true;
+        }
+        if self.eat('/') {
+            // NOTE transforms are not supported, variable value is inserted as is
+            if !self.skip_transform() {
+                return false;
+            }
+            self.text.push_str(&value.unwrap_or_default());
+            return true;
+        }
+        false
+    }
+
+    /// Parse choice options up to and including closing `|}` and return the first option.
+    fn parse_choice(&mut self) -> Option<String> {
+        let mut first = String::new();
+        let mut is_first = true;
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '\\' => match self.peek() {
+                    Some(next) if "$}\\,|".contains(next) => {
+                        self.pos += 1;
+                        if is_first {
+                            first.push(next);
+                        }
+                    }
+                    _ => {
+                        if is_first {
+                            first.push(c);
+                        }
+                    }
+                },
+                ',' => is_first = false,
+                '|' => {
+                    return if self.eat('}') { Some(first) } else { None };
+                }
+                _ => {
+                    if is_first {
+                        first.push(c);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Skip `regex/format/options}` part of the variable transform.
+    fn skip_transform(&mut self) -> bool {
+        let mut slashes = 0;
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '\\' => self.pos += 1,
+                '/' if slashes < 2 => slashes += 1,
+                '}' if slashes == 2 => return true,
+                _ => (),
+            }
+        }
+        false
+    }
+
+    fn resolve_variable(&self, name: &str) -> Option<String> {
+        let path = Path::new(self.buffile);
+        let value = match name {
+            "TM_FILENAME" => path.file_name()?.to_str()?,
+            "TM_FILENAME_BASE" => path.file_stem()?.to_str()?,
+            "TM_DIRECTORY" => path.parent()?.to_str()?,
+            "TM_FILEPATH" => self.buffile,
+            _ => return None,
+        };
+        Some(value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes() {
+        let snippet = expand(r"a\$b\}c\\d", "");
+        assert_eq!(snippet.text, r"a$b}c\d");
+        assert!(snippet.placeholders.is_empty());
+    }
+
+    #[test]
+    fn nested_placeholders() {
+        let snippet = expand("f(${1:a ${2:b}})", "");
+        assert_eq!(snippet.text, "f(a b)");
+        assert_eq!(snippet.placeholders, vec![(2, 5), (4, 5)]);
+    }
+
+    #[test]
+    fn choice_expands_into_first_option() {
+        let snippet = expand(r"${1|a\,b,c|} d", "");
+        assert_eq!(snippet.text, "a,b d");
+        assert_eq!(snippet.placeholders, vec![(0, 3)]);
+    }
+
+    #[test]
+    fn final_tabstop_goes_last() {
+        let snippet = expand("$0x${2:y}${1:z}$1", "");
+        assert_eq!(snippet.text, "xyz");
+        assert_eq!(snippet.placeholders, vec![(2, 3), (1, 2), (0, 0)]);
+    }
+
+    #[test]
+    fn offsets_are_in_bytes() {
+        let snippet = expand("ñ(${1:é}, $2)", "");
+        assert_eq!(snippet.text, "ñ(é, )");
+        assert_eq!(snippet.placeholders, vec![(3, 5), (7, 7)]);
+    }
+
+    #[test]
+    fn variables() {
+        let snippet = expand("$TM_FILENAME ${TM_FILENAME_BASE} ${UNKNOWN:x}", "/src/main.rs");
+        assert_eq!(snippet.text, "main.rs main x");
+    }
+
+    #[test]
+    fn malformed_constructs_are_literal() {
+        let snippet = expand("foo${1:bar", "");
+        assert_eq!(snippet.text, "foo${1:bar");
+        assert!(snippet.placeholders.is_empty());
+
+        let snippet = expand("a$ ${|x} ${1|y", "");
+        assert_eq!(snippet.text, "a$ ${|x} ${1|y");
+        assert!(snippet.placeholders.is_empty());
+    }
+}
@@ -0,0 +1,170 @@
+use context::*;
+use languageserver_types::*;
+use position::{character_to_byte, PositionConverter};
+use std::fs;
+use std::io;
+use std::path::Path;
+use types::*;
+use url::Url;
+
+fn escape(s: &str) -> String {
+    s.replace("'", "''")
+}
+
+/// Apply text edits to the file denoted by `uri`. Buffers opened in the editor are edited in place,
+/// other files are edited on disk. Returns false if edits failed to apply.
+pub fn apply_text_edits(
+    meta: &EditorMeta,
+    uri: &Url,
+    text_edits: &[TextEdit],
+    ctx: &mut Context,
+) -> bool {
+    if text_edits.is_empty() {
+        return true;
+    }
+    let path = uri.to_file_path();
+    if path.is_err() {
+        error!("Failed to convert uri to path: {}", uri);
+        return false;
+    }
+    let path = path.unwrap();
+    let buffile = path.to_str().unwrap();
+    if buffile == meta.buffile && meta.client.is_some() {
+        let command = apply_text_edits_to_buffer(buffile, text_edits, ctx);
+        ctx.exec(meta.clone(), command);
+    } else if ctx.versions.contains_key(buffile) {
+        let command = format!(
+            "eval -buffer %§{}§ '{}'",
+            buffile,
+            escape(&apply_text_edits_to_buffer(buffile, text_edits, ctx))
+        );
+        ctx.exec(meta.clone(), command);
+    } else if let Err(e) = apply_text_edits_to_file(&path, text_edits, ctx.position_encoding) {
+        error!("Failed to apply edits to file {}: {}", buffile, e);
+        return false;
+    }
+    true
+}
+
+/// Build editor command which applies text edits to the buffer.
+pub fn apply_text_edits_to_buffer(buffile: &str, text_edits: &[TextEdit], ctx: &Context) -> String {
+    let converter = PositionConverter::new(buffile, ctx);
+    let edits = text_edits
+        .iter()
+        .map(|text_edit| {
+            let TextEdit { range, new_text } = text_edit;
+            let range = converter.range_to_kakoune(*range);
+            // LSP ranges are 0-based, but Kakoune's 1-based.
+            // LSP ranges are exclusive, but Kakoune's are inclusive.
+            // Also from LSP spec: If you want to specify a range that contains a line including
+            // the line ending character(s) then use an end position denoting the start of the next
+            // line.
+            let mut start_line = range.start.line;
+            let mut start_char = range.start.character;
+            let mut end_line = range.end.line;
+            let mut end_char = range.end.character;
+
+            if start_line == end_line && start_char == end_char && start_char == 0 {
+                start_char = 1_000_000;
+            } else {
+                start_line += 1;
+                start_char += 1;
+            }
+
+            if end_char > 0 {
+                end_line += 1;
+            } else {
+                end_char = 1_000_000;
+            }
+
+            (
+                format!("{}.{}", start_line, start_char),
+                format!("{}.{}", end_line, end_char),
+                escape(&new_text),
+            )
+        })
+        .collect::<Vec<_>>();
+    let select_edits = edits
+        .iter()
+        .map(|(start, end, _)| format!("{},{}", start, end))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let apply_edits = edits
+        .iter()
+        .enumerate()
+        .map(|(i, (start, end, content))| {
+            format!(
+                "exec 'z{}<space>'
+                    {} '{}'",
+                if i > 0 {
+                    format!("{})", i)
+                } else {
+                    "".to_string()
+                },
+                if start == end {
+                    "lsp-insert-after-selection"
+                } else {
+                    "lsp-replace-selection"
+                },
+                content
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let command = format!(
+        "select {}
+            exec -save-regs '' Z
+            {}",
+        select_edits, apply_edits
+    );
+    format!("eval -draft -save-regs '^' '{}'", escape(&command))
+}
+
+/// Apply text edits to the file which is not opened in the editor.
+pub fn apply_text_edits_to_file(
+    path: &Path,
+    text_edits: &[TextEdit],
+    encoding: PositionEncoding,
+) -> io::Result<()> {
+    let mut text = fs::read_to_string(path)?;
+    let mut line_offsets = vec![0];
+    for (i, c) in text.char_indices() {
+        if c == '\n' {
+            line_offsets.push(i + 1);
+        }
+    }
+    let offset = |position: &Position| {
+        let line = position.line as usize;
+        if line >= line_offsets.len() {
+            return text.len();
+        }
+        let line_start = line_offsets[line];
+        let line_end = line_offsets
+            .get(line + 1)
+            .map(|next_line_start| next_line_start - 1)
+            .unwrap_or_else(|| text.len());
+        let column = character_to_byte(&text[line_start..line_end], position.character, encoding);
+        let mut offset = (line_start + column as usize).min(line_end);
+        while !text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    };
+    let mut edits = text_edits
+        .iter()
+        .map(|text_edit| {
+            (
+                offset(&text_edit.range.start),
+                offset(&text_edit.range.end),
+                &text_edit.new_text,
+            )
+        })
+        .collect::<Vec<_>>();
+    // NOTE sort is stable, so applying edits in reverse order keeps inserts at the same position
+    // in the order server sent them
+    edits.sort_by_key(|(start, end, _)| (*start, *end));
+    for (start, end, new_text) in edits.into_iter().rev() {
+        text.replace_range(start..end, new_text);
+    }
+    fs::write(path, text)
+}
@@ -1,6 +1,8 @@
 use context::*;
+use language_features::{formatting, semantic_tokens};
 use languageserver_types::notification::Notification;
 use languageserver_types::*;
+use position::byte_to_character;
 use serde::Deserialize;
 use std::fs::File;
 use std::io::Read;
@@ -19,6 +21,7 @@ pub fn text_document_did_open(_params: EditorParams, meta: &EditorMeta, ctx: &mu
         error!("Failed to read from file: {}", meta.buffile);
         return;
     }
+    let line_count = text.lines().count() as u64;
     let params = DidOpenTextDocumentParams {
         text_document: TextDocumentItem {
             uri: Url::from_file_path(&meta.buffile).unwrap(),
@@ -28,7 +31,11 @@ pub fn text_document_did_open(_params: EditorParams, meta: &EditorMeta, ctx: &mu
         },
     };
     ctx.versions.insert(meta.buffile.clone(), meta.version);
+    ctx.documents
+        .insert(meta.buffile.clone(), params.text_document.text.clone());
     ctx.notify(notification::DidOpenTextDocument::METHOD.into(), params);
+    formatting::setup_on_type_formatting(meta, ctx);
+    semantic_tokens::text_document_semantic_tokens(meta, line_count, ctx);
 }
 
 pub fn text_document_did_change(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
@@ -44,23 +51,100 @@ pub fn text_document_did_change(params: EditorParams, meta: &EditorMeta, ctx: &m
     if old_version >= version {
         return;
     }
+    let draft = params.draft;
+    // NOTE version is recorded only when the change is sent, otherwise responses computed for the
+    // previous version which the server still has would be considered stale
+    let content_change = match ctx.documents.get(&meta.buffile) {
+        Some(text) if *text == draft => return,
+        Some(text) if sync_kind(ctx) == TextDocumentSyncKind::Incremental => {
+            content_change(text, &draft, ctx.position_encoding)
+        }
+        _ => TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: draft.clone(),
+        },
+    };
     ctx.versions.insert(meta.buffile.clone(), version);
     ctx.diagnostics.insert(meta.buffile.clone(), Vec::new());
+    let line_count = draft.lines().count() as u64;
+    ctx.documents.insert(meta.buffile.clone(), draft);
     let params = DidChangeTextDocumentParams {
         text_document: VersionedTextDocumentIdentifier {
             uri,
             version: Some(meta.version),
         },
-        content_changes: vec![TextDocumentContentChangeEvent {
-            range: None,
-            range_length: None,
-            text: params.draft,
-        }],
+        content_changes: vec![content_change],
     };
     ctx.notify(notification::DidChangeTextDocument::METHOD.into(), params);
+    semantic_tokens::text_document_semantic_tokens(meta, line_count, ctx);
+}
+
+fn sync_kind(ctx: &Context) -> TextDocumentSyncKind {
+    match ctx
+        .capabilities
+        .as_ref()
+        .and_then(|x| x.text_document_sync.as_ref())
+    {
+        Some(TextDocumentSyncCapability::Kind(kind)) => *kind,
+        Some(TextDocumentSyncCapability::Options(options)) => {
+            options.change.unwrap_or(TextDocumentSyncKind::Full)
+        }
+        None => TextDocumentSyncKind::Full,
+    }
+}
+
+/// Build change event which replaces the only differing part of the old text with the new one.
+/// Common prefix and suffix are left intact, so typing produces small events.
+fn content_change(
+    old: &str,
+    new: &str,
+    encoding: PositionEncoding,
+) -> TextDocumentContentChangeEvent {
+    let mut prefix = old
+        .bytes()
+        .zip(new.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(prefix) || !new.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let mut suffix = old
+        .bytes()
+        .rev()
+        .zip(new.bytes().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+    let old_end = old.len() - suffix;
+    let removed = &old[prefix..old_end];
+    TextDocumentContentChangeEvent {
+        range: Some(Range::new(
+            offset_to_position(old, prefix, encoding),
+            offset_to_position(old, old_end, encoding),
+        )),
+        range_length: Some(byte_to_character(removed, removed.len() as u64, encoding)),
+        text: new[prefix..new.len() - suffix].to_string(),
+    }
+}
+
+/// Convert byte offset in the text into LSP position.
+fn offset_to_position(text: &str, offset: usize, encoding: PositionEncoding) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line = &before[line_start..];
+    Position::new(
+        before.matches('\n').count() as u64,
+        byte_to_character(line, line.len() as u64, encoding),
+    )
 }
 
 pub fn text_document_did_close(_params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    ctx.documents.remove(&meta.buffile);
     let uri = Url::from_file_path(&meta.buffile).unwrap();
     let params = DidCloseTextDocumentParams {
         text_document: TextDocumentIdentifier { uri },
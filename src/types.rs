@@ -1,10 +1,13 @@
 use fnv::FnvHashMap;
 use jsonrpc_core::{Call, Output, Params};
 use languageserver_types::*;
-use serde::Serialize;
-use serde_json::Value;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{self, Value};
 use std::io::Error;
 use toml;
+use url::Url;
+use url_serde;
 
 // Configuration
 
@@ -15,6 +18,8 @@ pub struct Config {
     pub server: ServerConfig,
     #[serde(default)]
     pub verbosity: u8,
+    #[serde(default)]
+    pub semantic_tokens: FnvHashMap<String, String>,
 }
 
 #[derive(Clone, Deserialize, Debug)]
@@ -110,13 +115,380 @@ pub struct TextDocumentCompletionParams {
     pub completion: EditorCompletion,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletionItemParams {
+    pub position: Position,
+    // NOTE text of the cursor line before cursor, used to find out which item is inserted
+    pub line_prefix: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CompletionItemIndexParams {
+    pub index: usize,
+    // NOTE item is resolved either to show its documentation or to apply its edits once accepted
+    pub accept: bool,
+}
+
+/// Completion items last sent to the editor along with the position where completion starts.
+#[derive(Debug, Default)]
+pub struct CompletionCache {
+    pub start: Position,
+    pub items: Vec<CompletionItem>,
+    // NOTE index of the item which is selected in the completion menu, if any
+    pub selected: Option<usize>,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct PositionParams {
     pub position: Position,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TextDocumentRangeFormattingParams {
+    pub formatting_options: FormattingOptions,
+    pub ranges: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TextDocumentOnTypeFormattingParams {
+    pub position: Position,
+    pub ch: String,
+    pub formatting_options: FormattingOptions,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchParams {
+    pub batch_id: u64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TextDocumentCodeActionParams {
+    pub selection_desc: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CodeActionSelectParams {
+    pub index: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeLensResolveParams {
+    pub batch_id: u64,
+    pub line: u64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApplyEditResultParams {
+    pub token: u64,
+    pub applied: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WorkspaceExecuteCommandParams {
+    pub command: String,
+    // NOTE arguments are passed from editor as a JSON array
+    #[serde(default)]
+    pub arguments: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TextDocumentRenameParams {
+    pub position: Position,
+    pub new_name: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TextDocumentFoldingRangeParams {
+    // NOTE innermost range around position is selected if it's given,
+    // otherwise all ranges of the kind are selected if it's given,
+    // otherwise folding structure is shown
+    #[serde(default)]
+    pub position: Option<Position>,
+    #[serde(default)]
+    pub kind: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TextDocumentSelectionRangeParams {
+    pub ranges: Vec<String>,
+}
+
+/// Selections before and after each expansion of selection ranges, used to shrink them back.
+#[derive(Debug, Default)]
+pub struct SelectionRangeHistory {
+    pub version: u64,
+    pub steps: Vec<(Vec<Range>, Vec<Range>)>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TextDocumentColorPresentationParams {
+    pub position: Position,
+    // NOTE text of the cursor line is used to find out which presentation is the current one
+    pub line_text: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TextDocumentCallHierarchyParams {
+    pub position: Position,
+    pub incoming: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct HierarchyExpandParams {
+    pub line: u64,
+}
+
+// NOTE node is identified by its item rather than by position in the hierarchy, because other
+// expansions could shift it while children are requested
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HierarchyNodeParams {
+    pub uri: String,
+    pub range: Range,
+}
+
+/// Call or type hierarchy shown in the editor, nodes are listed in the order of buffer lines along
+/// with their depth.
+#[derive(Debug, Default)]
+pub struct Hierarchy {
+    // NOTE method which requests children of the node, e.g. incoming calls or supertypes
+    pub method: String,
+    pub nodes: Vec<(usize, HierarchyItem)>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TextDocumentTypeHierarchyParams {
+    pub position: Position,
+    pub supertypes: bool,
+}
+
+/// Semantic tokens of the buffer as they were received last time, used to apply deltas.
+#[derive(Debug, Default)]
+pub struct SemanticTokensCache {
+    pub result_id: Option<String>,
+    pub data: Vec<u64>,
+    // NOTE used to request range covering the whole buffer on refresh
+    pub line_count: u64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TextDocumentInlayHintParams {
+    // NOTE 0-based lines of the visible part of the buffer, both inclusive
+    pub start_line: u64,
+    pub end_line: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct InlayHintResolveParams {
+    pub batch_id: u64,
+    pub line: u64,
+}
+
+/// Inlay hints of the buffer along with the range they were requested for.
+#[derive(Debug)]
+pub struct InlayHintsCache {
+    pub range: Range,
+    pub hints: Vec<InlayHint>,
+}
+
 // Language Server
 
+pub const GOTO_DECLARATION_METHOD: &str = "textDocument/declaration";
+pub const FOLDING_RANGE_METHOD: &str = "textDocument/foldingRange";
+pub const SELECTION_RANGE_METHOD: &str = "textDocument/selectionRange";
+pub const DOCUMENT_COLOR_METHOD: &str = "textDocument/documentColor";
+pub const COLOR_PRESENTATION_METHOD: &str = "textDocument/colorPresentation";
+pub const PREPARE_CALL_HIERARCHY_METHOD: &str = "textDocument/prepareCallHierarchy";
+pub const INCOMING_CALLS_METHOD: &str = "callHierarchy/incomingCalls";
+pub const OUTGOING_CALLS_METHOD: &str = "callHierarchy/outgoingCalls";
+pub const PREPARE_TYPE_HIERARCHY_METHOD: &str = "textDocument/prepareTypeHierarchy";
+pub const SUPERTYPES_METHOD: &str = "typeHierarchy/supertypes";
+pub const SUBTYPES_METHOD: &str = "typeHierarchy/subtypes";
+pub const SEMANTIC_TOKENS_FULL_METHOD: &str = "textDocument/semanticTokens/full";
+pub const SEMANTIC_TOKENS_FULL_DELTA_METHOD: &str = "textDocument/semanticTokens/full/delta";
+pub const SEMANTIC_TOKENS_RANGE_METHOD: &str = "textDocument/semanticTokens/range";
+pub const SEMANTIC_TOKENS_REFRESH_METHOD: &str = "workspace/semanticTokens/refresh";
+pub const INLAY_HINT_METHOD: &str = "textDocument/inlayHint";
+pub const INLAY_HINT_RESOLVE_METHOD: &str = "inlayHint/resolve";
+pub const INLAY_HINT_REFRESH_METHOD: &str = "workspace/inlayHint/refresh";
+
+/// Provider capability could be either a flag or registration options which imply support.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ProviderCapability {
+    Bool(bool),
+    // NOTE options content is not used, they only mark capability as supported
+    Options {},
+}
+
+impl ProviderCapability {
+    pub fn is_supported(&self) -> bool {
+        match self {
+            ProviderCapability::Bool(supported) => *supported,
+            ProviderCapability::Options {} => true,
+        }
+    }
+}
+
+impl Default for ProviderCapability {
+    fn default() -> Self {
+        ProviderCapability::Bool(false)
+    }
+}
+
+/// Units in which language server counts characters of positions.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PositionEncoding {
+    Utf8,
+    // NOTE UTF-16 is mandatory for all servers unless other encoding is negotiated
+    #[default]
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "utf-8" => Some(PositionEncoding::Utf8),
+            "utf-16" => Some(PositionEncoding::Utf16),
+            "utf-32" => Some(PositionEncoding::Utf32),
+            _ => None,
+        }
+    }
+}
+
+/// Server capabilities which are not covered by languageserver-types yet.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtendedServerCapabilities {
+    #[serde(default, deserialize_with = "deserialize_capability")]
+    pub call_hierarchy_provider: ProviderCapability,
+    // NOTE languageserver-types expects a flag, but servers reply with options when client
+    // supports code action literals
+    #[serde(default, deserialize_with = "deserialize_capability")]
+    pub code_action_provider: ProviderCapability,
+    #[serde(default, deserialize_with = "deserialize_capability")]
+    pub color_provider: ProviderCapability,
+    #[serde(default, deserialize_with = "deserialize_capability")]
+    pub declaration_provider: ProviderCapability,
+    #[serde(default, deserialize_with = "deserialize_capability")]
+    pub document_link_provider: Option<DocumentLinkOptions>,
+    #[serde(default, deserialize_with = "deserialize_capability")]
+    pub folding_range_provider: ProviderCapability,
+    #[serde(default, deserialize_with = "deserialize_capability")]
+    pub implementation_provider: ProviderCapability,
+    #[serde(default, deserialize_with = "deserialize_capability")]
+    pub inlay_hint_provider: Option<InlayHintProviderCapability>,
+    #[serde(default, deserialize_with = "deserialize_capability")]
+    pub position_encoding: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_capability")]
+    pub selection_range_provider: ProviderCapability,
+    #[serde(default, deserialize_with = "deserialize_capability")]
+    pub semantic_tokens_provider: Option<SemanticTokensOptions>,
+    #[serde(default, deserialize_with = "deserialize_capability")]
+    pub type_hierarchy_provider: ProviderCapability,
+    #[serde(default, deserialize_with = "deserialize_capability")]
+    pub type_definition_provider: ProviderCapability,
+}
+
+/// Deserialize capability falling back to default when it doesn't have expected shape, so one
+/// unexpected capability doesn't turn off the rest of them.
+fn deserialize_capability<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned + Default,
+{
+    let value = Value::deserialize(deserializer)?;
+    match serde_json::from_value(value.clone()) {
+        Ok(capability) => Ok(capability),
+        Err(e) => {
+            error!("Failed to parse server capability {}: {}", value, e);
+            Ok(T::default())
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentLinkOptions {
+    #[serde(default)]
+    pub resolve_provider: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensLegend {
+    pub token_types: Vec<String>,
+    pub token_modifiers: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum SemanticTokensFullOptions {
+    Bool(bool),
+    Options {
+        #[serde(default)]
+        delta: Option<bool>,
+    },
+}
+
+impl Default for SemanticTokensFullOptions {
+    fn default() -> Self {
+        SemanticTokensFullOptions::Bool(false)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensOptions {
+    pub legend: SemanticTokensLegend,
+    #[serde(default)]
+    pub range: ProviderCapability,
+    #[serde(default)]
+    pub full: SemanticTokensFullOptions,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlayHintOptions {
+    #[serde(default)]
+    pub resolve_provider: Option<bool>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum InlayHintProviderCapability {
+    Bool(bool),
+    Options(InlayHintOptions),
+}
+
+impl InlayHintProviderCapability {
+    pub fn is_supported(&self) -> bool {
+        match self {
+            InlayHintProviderCapability::Bool(supported) => *supported,
+            InlayHintProviderCapability::Options(_) => true,
+        }
+    }
+
+    pub fn resolve_provider(&self) -> bool {
+        match self {
+            InlayHintProviderCapability::Bool(_) => false,
+            InlayHintProviderCapability::Options(options) => {
+                options.resolve_provider.unwrap_or(false)
+            }
+        }
+    }
+}
+
 // XXX serde(untagged) ?
 #[derive(Debug)]
 pub enum ServerMessage {
@@ -169,3 +541,233 @@ pub enum TextEditResponse {
     None,
     Array(Vec<TextEdit>),
 }
+
+// NOTE languageserver-types supports only commands as a code action response,
+// but newer servers reply with code action literals as well
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeAction {
+    pub title: String,
+    #[serde(default)]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub diagnostics: Option<Vec<Diagnostic>>,
+    #[serde(default)]
+    pub edit: Option<WorkspaceEdit>,
+    #[serde(default)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CodeActionOrCommand {
+    Command(Command),
+    CodeAction(CodeAction),
+}
+
+// NOTE languageserver-types requires link target, but servers could omit it and provide it only
+// on documentLink/resolve
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentLink {
+    pub range: Range,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FoldingRangeParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FoldingRange {
+    pub start_line: u64,
+    #[serde(default)]
+    pub start_character: Option<u64>,
+    pub end_line: u64,
+    #[serde(default)]
+    pub end_character: Option<u64>,
+    #[serde(default)]
+    pub kind: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectionRangeParams {
+    pub text_document: TextDocumentIdentifier,
+    pub positions: Vec<Position>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SelectionRange {
+    pub range: Range,
+    #[serde(default)]
+    pub parent: Option<Box<SelectionRange>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentColorParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Color {
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+    pub alpha: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ColorInformation {
+    pub range: Range,
+    pub color: Color,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColorPresentationParams {
+    pub text_document: TextDocumentIdentifier,
+    pub color: Color,
+    pub range: Range,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColorPresentation {
+    pub label: String,
+    #[serde(default)]
+    pub text_edit: Option<TextEdit>,
+    #[serde(default)]
+    pub additional_text_edits: Option<Vec<TextEdit>>,
+}
+
+// NOTE call and type hierarchy items have the same structure
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HierarchyItem {
+    pub name: String,
+    pub kind: SymbolKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<u8>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(with = "url_serde")]
+    pub uri: Url,
+    pub range: Range,
+    pub selection_range: Range,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HierarchyItemParams {
+    pub item: HierarchyItem,
+}
+
+// NOTE ranges of calls are omitted, only callers and callees are shown
+#[derive(Debug, Deserialize)]
+pub struct CallHierarchyIncomingCall {
+    pub from: HierarchyItem,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallHierarchyOutgoingCall {
+    pub to: HierarchyItem,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensDeltaParams {
+    pub text_document: TextDocumentIdentifier,
+    pub previous_result_id: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensRangeParams {
+    pub text_document: TextDocumentIdentifier,
+    pub range: Range,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokens {
+    #[serde(default)]
+    pub result_id: Option<String>,
+    pub data: Vec<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensEdit {
+    pub start: usize,
+    pub delete_count: usize,
+    #[serde(default)]
+    pub data: Option<Vec<u64>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensDelta {
+    #[serde(default)]
+    pub result_id: Option<String>,
+    pub edits: Vec<SemanticTokensEdit>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum SemanticTokensFullDeltaResult {
+    Delta(SemanticTokensDelta),
+    Tokens(SemanticTokens),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlayHintParams {
+    pub text_document: TextDocumentIdentifier,
+    pub range: Range,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InlayHint {
+    pub position: Position,
+    pub label: InlayHintLabel,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tooltip: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub padding_left: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub padding_right: Option<bool>,
+    // NOTE the rest of the hint is not used, but it's kept to send the hint back on resolve
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum InlayHintLabel {
+    String(String),
+    Parts(Vec<InlayHintLabelPart>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InlayHintLabelPart {
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tooltip: Option<Value>,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, Value>,
+}
@@ -1,3 +1,4 @@
+use languageserver_types::{Position, Range};
 use std::os::unix::fs::DirBuilderExt;
 use std::{env, fs, path};
 
@@ -11,3 +12,49 @@ pub fn sock_dir() -> path::PathBuf {
         .unwrap();
     path
 }
+
+/// Convert Kakoune selection description `anchor_line.anchor_column,cursor_line.cursor_column`
+/// into LSP range. Kakoune coordinates are 1-based and inclusive, LSP's are 0-based and exclusive.
+pub fn parse_kakoune_range(desc: &str) -> Option<Range> {
+    let mut coords = desc.split(',').map(|coord| {
+        let mut parts = coord.split('.').map(|x| x.parse::<u64>().ok());
+        match (parts.next(), parts.next()) {
+            (Some(Some(line)), Some(Some(column))) if line > 0 && column > 0 => {
+                Some(Position::new(line - 1, column - 1))
+            }
+            _ => None,
+        }
+    });
+    let anchor = coords.next()??;
+    let cursor = coords.next()??;
+    let (start, mut end) = if anchor <= cursor {
+        (anchor, cursor)
+    } else {
+        (cursor, anchor)
+    };
+    end.character += 1;
+    Some(Range::new(start, end))
+}
+
+/// Convert LSP range into Kakoune range description.
+pub fn lsp_range_to_kakoune(range: &Range) -> String {
+    // LSP ranges are 0-based, but Kakoune's 1-based.
+    // LSP ranges are exclusive, but Kakoune's are inclusive.
+    // Also from LSP spec: If you want to specify a range that contains a line including
+    // the line ending character(s) then use an end position denoting the start of the next
+    // line.
+    let mut end_line = range.end.line;
+    let mut end_char = range.end.character;
+    if end_char > 0 {
+        end_line += 1;
+    } else {
+        end_char = 1_000_000;
+    }
+    format!(
+        "{}.{},{}.{}",
+        range.start.line + 1,
+        range.start.character + 1,
+        end_line,
+        end_char
+    )
+}
@@ -0,0 +1,209 @@
+use context::*;
+use jsonrpc_core::{Id, Params};
+use languageserver_types::request::Request;
+use languageserver_types::*;
+use serde::Deserialize;
+use serde_json::{self, Value};
+use text_edit::{apply_text_edits, apply_text_edits_to_buffer, apply_text_edits_to_file};
+use toml;
+use types::*;
+
+/// Apply workspace edit to all affected files. Returns false if any of edits failed to apply.
+pub fn apply_edit(meta: &EditorMeta, edit: WorkspaceEdit, ctx: &mut Context) -> bool {
+    let mut applied = true;
+    // NOTE documentChanges are preferred over changes if server provides both
+    if let Some(document_changes) = edit.document_changes {
+        for change in document_changes {
+            applied &= apply_text_edits(meta, &change.text_document.uri, &change.edits, ctx);
+        }
+    } else if let Some(changes) = edit.changes {
+        for (uri, text_edits) in &changes {
+            applied &= apply_text_edits(meta, uri, text_edits, ctx);
+        }
+    }
+    applied
+}
+
+fn escape(s: &str) -> String {
+    s.replace("'", "''")
+}
+
+/// Apply workspace edit requested by language server. Edits of buffers are sent to the editor
+/// which reports back whether they were applied. Files which are not opened in the editor are
+/// edited on disk only after that, so a failed buffer edit leaves them untouched.
+pub fn apply_edit_from_server(id: Id, params: Params, ctx: &mut Context) {
+    let params: ApplyWorkspaceEditParams = match params.parse() {
+        Ok(params) => params,
+        Err(e) => {
+            ctx.reply(id, Err(e));
+            return;
+        }
+    };
+    let changes = if let Some(document_changes) = params.edit.document_changes {
+        document_changes
+            .into_iter()
+            .map(|change| (change.text_document.uri, change.edits))
+            .collect()
+    } else {
+        params
+            .edit
+            .changes
+            .unwrap_or_default()
+            .into_iter()
+            .collect::<Vec<_>>()
+    };
+    let mut buffer_commands = vec![];
+    let mut file_edits = vec![];
+    let mut buffile = None;
+    for (uri, text_edits) in changes {
+        if text_edits.is_empty() {
+            continue;
+        }
+        let path = match uri.to_file_path() {
+            Ok(path) => path,
+            Err(_) => {
+                let reason = format!("Failed to convert uri to path: {}", uri);
+                reply_apply_edit(id, false, Some(reason), ctx);
+                return;
+            }
+        };
+        let path_str = path.to_str().unwrap().to_string();
+        if ctx.versions.contains_key(&path_str) {
+            buffer_commands.push(format!(
+                "eval -buffer %§{}§ '{}'",
+                path_str,
+                escape(&apply_text_edits_to_buffer(&path_str, &text_edits, ctx))
+            ));
+            buffile = Some(path_str);
+        } else {
+            file_edits.push((path, text_edits));
+        }
+    }
+    let buffile = match buffile {
+        Some(buffile) => buffile,
+        None => {
+            apply_file_edits(id, file_edits, false, ctx);
+            return;
+        }
+    };
+    let token = ctx.request_counter;
+    ctx.request_counter += 1;
+    ctx.pending_server_requests.insert(token, (id, file_edits));
+    // NOTE result is reported from one of edited buffers, so editor request is routed back to
+    // this controller
+    let command = format!(
+        "try '{}' catch 'lsp-apply-edit-result {} false'",
+        escape(&format!(
+            "{}\nlsp-apply-edit-result {} true",
+            buffer_commands.join("\n"),
+            token
+        )),
+        token
+    );
+    let command = format!("eval -buffer %§{}§ '{}'", buffile, escape(&command));
+    let meta = EditorMeta {
+        session: ctx.session.clone(),
+        client: None,
+        buffile,
+        version: 0,
+    };
+    ctx.exec(meta, command);
+}
+
+/// Reply to language server once editor reported result of applying workspace edit.
+pub fn apply_edit_result(params: EditorParams, ctx: &mut Context) {
+    let params = ApplyEditResultParams::deserialize(params);
+    if params.is_err() {
+        error!("Params should follow ApplyEditResultParams structure");
+        return;
+    }
+    let params = params.unwrap();
+    let (id, file_edits) = match ctx.pending_server_requests.remove(&params.token) {
+        Some(request) => request,
+        None => {
+            error!("No pending workspace edit {}", params.token);
+            return;
+        }
+    };
+    if params.applied {
+        apply_file_edits(id, file_edits, true, ctx);
+    } else {
+        let reason = "Editor failed to apply edits, no files were changed".to_string();
+        reply_apply_edit(id, false, Some(reason), ctx);
+    }
+}
+
+/// Apply edits to files on disk and reply to language server. Edits which were already applied
+/// are not rolled back on failure, so the failure reason lists them.
+fn apply_file_edits(id: Id, file_edits: FileEdits, buffers_edited: bool, ctx: &mut Context) {
+    let mut edited = vec![];
+    if buffers_edited {
+        edited.push("buffers opened in the editor".to_string());
+    }
+    for (path, text_edits) in file_edits {
+        if let Err(e) = apply_text_edits_to_file(&path, &text_edits, ctx.position_encoding) {
+            let mut reason = format!("Failed to apply edits to file {}: {}", path.display(), e);
+            if !edited.is_empty() {
+                reason.push_str(&format!(", already edited: {}", edited.join(", ")));
+            }
+            reply_apply_edit(id, false, Some(reason), ctx);
+            return;
+        }
+        edited.push(path.display().to_string());
+    }
+    reply_apply_edit(id, true, None, ctx);
+}
+
+fn reply_apply_edit(id: Id, applied: bool, failure_reason: Option<String>, ctx: &mut Context) {
+    // NOTE languageserver-types lacks failureReason field
+    let mut response = json!({ "applied": applied });
+    if let Some(failure_reason) = failure_reason {
+        warn!("Workspace edit is not applied: {}", failure_reason);
+        response["failureReason"] = json!(failure_reason);
+    }
+    ctx.reply(id, Ok(response));
+}
+
+pub fn execute_command(meta: &EditorMeta, command: Command, ctx: &mut Context) {
+    let req_params = ExecuteCommandParams {
+        command: command.command,
+        arguments: command.arguments.unwrap_or_default(),
+    };
+    let id = ctx.next_request_id();
+    ctx.response_waitlist.insert(
+        id.clone(),
+        (
+            meta.clone(),
+            request::ExecuteCommand::METHOD.into(),
+            toml::Value::Table(toml::value::Table::default()),
+        ),
+    );
+    ctx.call(id, request::ExecuteCommand::METHOD.into(), req_params);
+}
+
+pub fn workspace_execute_command(params: EditorParams, meta: &EditorMeta, ctx: &mut Context) {
+    let params = WorkspaceExecuteCommandParams::deserialize(params);
+    if params.is_err() {
+        error!("Params should follow WorkspaceExecuteCommandParams structure");
+        return;
+    }
+    let params = params.unwrap();
+    let arguments = if params.arguments.trim().is_empty() {
+        Ok(vec![])
+    } else {
+        serde_json::from_str::<Vec<Value>>(&params.arguments)
+    };
+    if arguments.is_err() {
+        ctx.exec(
+            meta.clone(),
+            "lsp-show-error 'Command arguments should be a JSON array'".to_string(),
+        );
+        return;
+    }
+    let command = Command {
+        title: params.command.clone(),
+        command: params.command,
+        arguments: Some(arguments.unwrap()),
+    };
+    execute_command(meta, command, ctx);
+}